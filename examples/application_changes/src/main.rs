@@ -10,7 +10,7 @@ use std::time;
 
 use sysrepo::connection::{ConnectionOptions, SrConnection};
 use sysrepo::enums::{SrDatastore, SrLogLevel};
-use sysrepo::errors::SrError;
+use sysrepo::errors::SrErrorInfo;
 use sysrepo::session::{SrChangeOperation, SrEvent, SrSession};
 use sysrepo::*;
 use sysrepo_sys::sr_val_t;
@@ -120,13 +120,13 @@ fn run() -> bool {
     println!("");
     print_current_config(&mut sess, &mod_name);
 
-    let f = |mut sess: SrSession,
+    let f = |sess: &mut SrSession,
              sub_id: u32,
              mod_name: &str,
              _path: Option<&str>,
              event: SrEvent,
              _request_id: u32|
-     -> Result<(), SrError> {
+     -> Result<(), SrErrorInfo> {
         let path = "//.";
         let mut iter = match sess.get_changes_iter(&path) {
             Ok(iter) => iter,
@@ -153,7 +153,7 @@ fn run() -> bool {
             println!("");
             println!(" ========== CONFIG HAS CHANGED, CURRENT RUNNING CONFIG: ==========");
             println!("");
-            print_current_config(&mut sess, mod_name);
+            print_current_config(sess, mod_name);
         }
         return Ok(());
     };
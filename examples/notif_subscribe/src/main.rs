@@ -12,7 +12,6 @@ use sysrepo::enums::{SrDatastore, SrLogLevel, SrNotifType};
 use sysrepo::session::SrSession;
 use sysrepo::values::SrValues;
 use sysrepo::*;
-use sysrepo_sys::timespec;
 use utils::*;
 
 /// Show help.
@@ -69,12 +68,12 @@ fn run() -> bool {
     };
 
     // Callback function.
-    let f = |_sess: SrSession,
+    let f = |_sess: &mut SrSession,
              sub_id: u32,
              _notif_type: SrNotifType,
              path: Option<&str>,
              values: SrValues,
-             _timestamp: *mut timespec| {
+             _timestamp: Option<time::SystemTime>| {
         let path = path.unwrap_or("");
         println!("");
         println!("");
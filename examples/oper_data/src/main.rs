@@ -6,13 +6,12 @@
 use std::env;
 use std::thread;
 use std::time;
-use sysrepo::connection::{ConnectionOptions, SrConnection};
+use sysrepo::connection::{ConnectionOptions, ContextGuard, SrConnection};
 use sysrepo::enums::{SrDatastore, SrLogLevel};
-use sysrepo::errors::SrError;
+use sysrepo::errors::SrErrorInfo;
 use sysrepo::session::SrSession;
 use sysrepo::*;
 use utils::*;
-use yang3::context::Context;
 use yang3::data::DataTree;
 
 /// Show help.
@@ -69,14 +68,14 @@ fn run() -> bool {
         &mod_name,
         &path,
         |session: &mut SrSession,
-         ctx: &Context,
+         ctx: &ContextGuard,
          sub_id: u32,
          mod_name: &str,
          path: &str,
          _request_xpath: Option<&str>,
          _request_id: u32,
          _node_opt|
-         -> Result<Option<DataTree>, SrError> {
+         -> Result<Option<DataTree>, SrErrorInfo> {
             println!("");
             println!("");
             println!(
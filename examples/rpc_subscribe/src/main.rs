@@ -9,6 +9,7 @@ use std::time;
 
 use sysrepo::connection::{ConnectionOptions, SrConnection};
 use sysrepo::enums::{SrDatastore, SrLogLevel};
+use sysrepo::errors::SrErrorInfo;
 use sysrepo::session::{SrEvent, SrSession};
 use sysrepo::value::Data;
 use sysrepo::values::SrValues;
@@ -58,13 +59,13 @@ fn run() -> bool {
     };
 
     // Callback function.
-    let f = |_sess: SrSession,
+    let f = |_sess: &mut SrSession,
              _sub_id: u32,
              _op_path: &str,
              _inputs: SrValues,
              _event: SrEvent,
              _request_id: u32|
-     -> SrValues {
+     -> Result<SrValues, SrErrorInfo> {
         let mut sr_output = SrValues::new(1, false);
         sr_output
             .add_value(
@@ -74,7 +75,7 @@ fn run() -> bool {
                 false,
             )
             .expect("Could not add value");
-        sr_output
+        Ok(sr_output)
     };
 
     // Subscribe for the RPC.
@@ -0,0 +1,501 @@
+//! Tokio driven subscriptions.
+//!
+//! Subscriptions created here use `SrSubcribeFlag::NoThread`, so sysrepo
+//! does not start a thread for them. [`SrSubscription::run`] waits on the
+//! subscription event pipe and processes the events on the blocking pool,
+//! which lets the callbacks be async closures. Sysrepo needs the result of
+//! a callback before the event processing returns, so each callback future
+//! is run to completion on the blocking thread, never on a runtime worker.
+//! Any runtime flavor works; without a runtime subscribing fails with
+//! `SrError::Unsupported`.
+
+use crate::connection::ContextGuard;
+use crate::enums::{SrNotifType, SrSubcribeFlag};
+use crate::errors::{SrError, SrErrorInfo};
+use crate::session::{SrEvent, SrSession};
+use crate::subscription::SrSubscription;
+use crate::values::SrValues;
+use libc::c_int;
+use std::time::SystemTime;
+use sysrepo_sys as ffi_sys;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::runtime::Handle;
+use yang3::data::DataTree;
+
+const NO_THREAD: ffi_sys::sr_subscr_options_t =
+    SrSubcribeFlag::NoThread as ffi_sys::sr_subscr_options_t;
+
+/// Get the runtime the callbacks are run on.
+fn runtime() -> Result<Handle, SrError> {
+    Handle::try_current().map_err(|_| SrError::Unsupported)
+}
+
+impl SrSubscription {
+    /// Process the subscription events as they arrive, usually spawned with
+    /// `tokio::spawn(subscription.run())`. Runs until an error occurs; the
+    /// subscription is removed when the future is dropped.
+    pub async fn run(self) -> Result<(), SrError> {
+        let mut subscription = self;
+        let fd = AsyncFd::with_interest(
+            subscription.event_pipe()?,
+            Interest::READABLE,
+        )
+        .map_err(|_| SrError::Sys)?;
+
+        loop {
+            let mut guard = fd.readable().await.map_err(|_| SrError::Sys)?;
+            let (sub, result) = tokio::task::spawn_blocking(move || {
+                let result = subscription.process_events();
+                (subscription, result)
+            })
+            .await
+            .map_err(|_| SrError::Sys)?;
+            subscription = sub;
+            result?;
+            guard.clear_ready();
+        }
+    }
+
+    /// Subscribe module change with an async callback.
+    pub fn on_module_change_async<F>(
+        session: &SrSession,
+        module_name: &str,
+        xpath: Option<&str>,
+        mut callback: F,
+        priority: u32,
+        options: ffi_sys::sr_subscr_options_t,
+    ) -> Result<Self, SrError>
+    where
+        F: AsyncFnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                Option<&str>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let handle = runtime()?;
+
+        Self::on_module_change(
+            session,
+            module_name,
+            xpath,
+            move |sess: &mut SrSession,
+                  sub_id: u32,
+                  module_name: &str,
+                  xpath: Option<&str>,
+                  event: SrEvent,
+                  request_id: u32| {
+                handle.block_on(callback(
+                    sess,
+                    sub_id,
+                    module_name,
+                    xpath,
+                    event,
+                    request_id,
+                ))
+            },
+            priority,
+            options | NO_THREAD,
+        )
+    }
+
+    /// Subscribe operational data provider with an async callback.
+    pub fn on_oper_get_subscribe_async<F>(
+        session: &SrSession,
+        module_name: &str,
+        xpath: &str,
+        mut callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<Self, SrError>
+    where
+        F: for<'a> AsyncFnMut(
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &'a str,
+                &'a str,
+                Option<&'a str>,
+                u32,
+                Option<&'a mut DataTree<'a>>,
+            )
+                -> Result<Option<DataTree<'a>>, SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let handle = runtime()?;
+
+        Self::on_oper_get_subscribe(
+            session,
+            module_name,
+            xpath,
+            move |sess, ctx, sub_id, module_name, path, request, id, parent| {
+                handle.block_on(callback(
+                    sess,
+                    ctx,
+                    sub_id,
+                    module_name,
+                    path,
+                    request,
+                    id,
+                    parent,
+                ))
+            },
+            opts | NO_THREAD,
+        )
+    }
+
+    /// Subscribe RPC with an async callback on values.
+    pub fn on_rpc_subscribe_async<F>(
+        session: &SrSession,
+        xpath: Option<&str>,
+        mut callback: F,
+        priority: u32,
+        options: ffi_sys::sr_subscr_options_t,
+    ) -> Result<Self, SrError>
+    where
+        F: AsyncFnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                SrValues,
+                SrEvent,
+                u32,
+            ) -> Result<SrValues, SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let handle = runtime()?;
+
+        Self::on_rpc_subscribe(
+            session,
+            xpath,
+            move |sess: &mut SrSession,
+                  sub_id: u32,
+                  op_path: &str,
+                  inputs: SrValues,
+                  event: SrEvent,
+                  request_id: u32| {
+                handle.block_on(callback(
+                    sess, sub_id, op_path, inputs, event, request_id,
+                ))
+            },
+            priority,
+            options | NO_THREAD,
+        )
+    }
+
+    /// Subscribe RPC with an async callback.
+    pub fn on_rpc_subscribe_tree_async<F>(
+        session: &SrSession,
+        xpath: Option<&str>,
+        mut callback: F,
+        priority: u32,
+        options: ffi_sys::sr_subscr_options_t,
+    ) -> Result<Self, SrError>
+    where
+        F: for<'a> AsyncFnMut(
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &str,
+                &DataTree<'a>,
+                &mut DataTree<'a>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let handle = runtime()?;
+
+        Self::on_rpc_subscribe_tree(
+            session,
+            xpath,
+            move |sess, ctx, sub_id, op_path, input, output, event, id| {
+                handle.block_on(callback(
+                    sess, ctx, sub_id, op_path, input, output, event, id,
+                ))
+            },
+            priority,
+            options | NO_THREAD,
+        )
+    }
+
+    /// Subscribe event notification with an async callback on values.
+    pub fn on_notification_subscribe_async<F>(
+        session: &SrSession,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        mut callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<Self, SrError>
+    where
+        F: AsyncFnMut(
+                &mut SrSession,
+                u32,
+                SrNotifType,
+                Option<&str>,
+                SrValues,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
+    {
+        let handle = runtime()?;
+
+        Self::on_notification_subscribe(
+            session,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            move |sess: &mut SrSession,
+                  sub_id: u32,
+                  notif_type: SrNotifType,
+                  xpath: Option<&str>,
+                  values: SrValues,
+                  timestamp: Option<SystemTime>| {
+                handle.block_on(callback(
+                    sess, sub_id, notif_type, xpath, values, timestamp,
+                ))
+            },
+            opts | NO_THREAD,
+        )
+    }
+
+    /// Subscribe event notification with an async callback.
+    pub fn on_notification_subscribe_tree_async<F>(
+        session: &SrSession,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        mut callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<Self, SrError>
+    where
+        F: AsyncFnMut(
+                &SrSession,
+                u32,
+                SrNotifType,
+                &DataTree<'_>,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
+    {
+        let handle = runtime()?;
+
+        Self::on_notification_subscribe_tree(
+            session,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            move |sess: &SrSession,
+                  sub_id: u32,
+                  notif_type: SrNotifType,
+                  notif: &DataTree<'_>,
+                  timestamp: Option<SystemTime>| {
+                handle.block_on(callback(
+                    sess, sub_id, notif_type, notif, timestamp,
+                ))
+            },
+            opts | NO_THREAD,
+        )
+    }
+}
+
+// The subscriptions are returned instead of kept by the session, so that
+// running one does not borrow the session.
+impl SrSession {
+    /// Subscribe module change with an async callback, drive it with
+    /// [`SrSubscription::run`].
+    pub fn on_module_change_subscribe_async<F>(
+        &self,
+        mod_name: &str,
+        path: Option<&str>,
+        callback: F,
+        priority: u32,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<SrSubscription, SrErrorInfo>
+    where
+        F: AsyncFnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                Option<&str>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        SrSubscription::on_module_change_async(
+            self, mod_name, path, callback, priority, opts,
+        )
+        .map_err(|err| self.error(err as c_int))
+    }
+
+    /// Subscribe operational data provider with an async callback, drive it
+    /// with [`SrSubscription::run`].
+    pub fn on_oper_get_subscribe_async<F>(
+        &self,
+        module_name: &str,
+        xpath: &str,
+        callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<SrSubscription, SrErrorInfo>
+    where
+        F: for<'a> AsyncFnMut(
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &'a str,
+                &'a str,
+                Option<&'a str>,
+                u32,
+                Option<&'a mut DataTree<'a>>,
+            )
+                -> Result<Option<DataTree<'a>>, SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        SrSubscription::on_oper_get_subscribe_async(
+            self,
+            module_name,
+            xpath,
+            callback,
+            opts,
+        )
+        .map_err(|err| self.error(err as c_int))
+    }
+
+    /// Subscribe RPC with an async callback on values, drive it with
+    /// [`SrSubscription::run`].
+    pub fn on_rpc_subscribe_async<F>(
+        &self,
+        xpath: Option<&str>,
+        callback: F,
+        priority: u32,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<SrSubscription, SrErrorInfo>
+    where
+        F: AsyncFnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                SrValues,
+                SrEvent,
+                u32,
+            ) -> Result<SrValues, SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        SrSubscription::on_rpc_subscribe_async(
+            self, xpath, callback, priority, opts,
+        )
+        .map_err(|err| self.error(err as c_int))
+    }
+
+    /// Subscribe RPC with an async callback, drive it with
+    /// [`SrSubscription::run`].
+    pub fn on_rpc_subscribe_tree_async<F>(
+        &self,
+        xpath: Option<&str>,
+        callback: F,
+        priority: u32,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<SrSubscription, SrErrorInfo>
+    where
+        F: for<'a> AsyncFnMut(
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &str,
+                &DataTree<'a>,
+                &mut DataTree<'a>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        SrSubscription::on_rpc_subscribe_tree_async(
+            self, xpath, callback, priority, opts,
+        )
+        .map_err(|err| self.error(err as c_int))
+    }
+
+    /// Subscribe event notification with an async callback on values, drive
+    /// it with [`SrSubscription::run`].
+    pub fn on_notif_subscribe_async<F>(
+        &self,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<SrSubscription, SrErrorInfo>
+    where
+        F: AsyncFnMut(
+                &mut SrSession,
+                u32,
+                SrNotifType,
+                Option<&str>,
+                SrValues,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
+    {
+        SrSubscription::on_notification_subscribe_async(
+            self,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            callback,
+            opts,
+        )
+        .map_err(|err| self.error(err as c_int))
+    }
+
+    /// Subscribe event notification with an async callback, drive it with
+    /// [`SrSubscription::run`].
+    pub fn on_notif_subscribe_tree_async<F>(
+        &self,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<SrSubscription, SrErrorInfo>
+    where
+        F: AsyncFnMut(
+                &SrSession,
+                u32,
+                SrNotifType,
+                &DataTree<'_>,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
+    {
+        SrSubscription::on_notification_subscribe_tree_async(
+            self,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            callback,
+            opts,
+        )
+        .map_err(|err| self.error(err as c_int))
+    }
+}
@@ -2,6 +2,8 @@ use crate::errors::SrError;
 use libc::strdup;
 use std::ffi::CString;
 use std::os::raw::c_char;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use sysrepo_sys as ffi_sys;
 
 pub fn str_to_cstring(s: &str) -> Result<CString, SrError> {
     CString::new(s).map_err(|_| SrError::InvalArg)
@@ -11,3 +13,60 @@ pub fn dup_str(s: &str) -> Result<*mut c_char, SrError> {
     let s = unsafe { strdup(str_to_cstring(s)?.as_ptr()) };
     Ok(s)
 }
+
+/// Strip list predicates from a data path so it can be used as schema path.
+pub fn schema_path(path: &str) -> String {
+    let mut schema_path = String::with_capacity(path.len());
+    let mut depth = 0;
+    let mut quote = None;
+    for c in path.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') if depth > 0 => quote = Some(c),
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            (None, c) if depth == 0 => schema_path.push(c),
+            _ => {}
+        }
+    }
+    schema_path
+}
+
+/// Convert a sysrepo timespec into SystemTime.
+pub fn timespec_to_system_time(ts: &ffi_sys::timespec) -> SystemTime {
+    UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Convert SystemTime into a sysrepo timespec.
+pub fn system_time_to_timespec(time: SystemTime) -> ffi_sys::timespec {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    ffi_sys::timespec {
+        tv_sec: since_epoch.as_secs() as _,
+        tv_nsec: since_epoch.subsec_nanos() as _,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_path_strips_predicates() {
+        assert_eq!(
+            schema_path("/m:cont/list[name='a]b'][id=\"1\"]/val"),
+            "/m:cont/list/val"
+        );
+        assert_eq!(schema_path("/m:leaf-list[.='x']"), "/m:leaf-list");
+        assert_eq!(schema_path("/m:leaf"), "/m:leaf");
+    }
+
+    #[test]
+    fn timespec_round_trip_successful() {
+        let time = UNIX_EPOCH + Duration::new(1_700_000_000, 42);
+        let ts = system_time_to_timespec(time);
+        assert_eq!(ts.tv_sec, 1_700_000_000);
+        assert_eq!(ts.tv_nsec, 42);
+        assert_eq!(timespec_to_system_time(&ts), time);
+    }
+}
@@ -0,0 +1,295 @@
+//! NETCONF confirmed commit of the candidate datastore.
+//!
+//! [`SrConfirmedCommit::commit_confirmed`] snapshots running, copies candidate
+//! to running and starts a timer. Unless [`SrConfirmedCommit::confirm`] is
+//! called before it expires, running is restored from the snapshot, as it is
+//! by [`SrConfirmedCommit::cancel`].
+//!
+//! Unlike RFC 6241, a commit with a persist ID can only be confirmed through
+//! the handle that made it. When the handle is dropped, the commit is kept
+//! until its timeout and then rolled back.
+
+use crate::connection::SrConnection;
+use crate::enums::{SrDatastore, SrGetOptions};
+use crate::errors::{SrError, SrErrorInfo};
+use crate::session::SrSession;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use yang3::data::{
+    Data, DataFormat, DataParserFlags, DataPrinterFlags, DataTree,
+    DataValidationFlags,
+};
+
+/// Commit waiting for its confirmation.
+struct Pending {
+    /// Running before the commit, `None` if it was empty.
+    snapshot: Option<String>,
+    persist: Option<String>,
+    deadline: Instant,
+    /// Error of the rollback on timeout, which is not retried.
+    error: Option<SrErrorInfo>,
+}
+
+struct State {
+    session: SrSession,
+    pending: Option<Pending>,
+    shutdown: bool,
+    /// The handle was dropped, stop once nothing is pending.
+    detached: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    timer: Condvar,
+}
+
+/// Confirmed commit of the candidate datastore to running. Dropping it rolls
+/// back a pending commit without a persist ID, ignoring errors, use
+/// [`SrConfirmedCommit::close`] to get them.
+pub struct SrConfirmedCommit {
+    shared: Arc<Shared>,
+    timer: Option<JoinHandle<()>>,
+}
+
+impl State {
+    /// Print the running datastore.
+    fn snapshot(&mut self) -> Result<Option<String>, SrErrorInfo> {
+        let ctx = self.session.get_context();
+        let data = match self.session.get_data(
+            &ctx,
+            "/*",
+            0,
+            None,
+            SrGetOptions::SR_OPER_DEFAULT,
+        ) {
+            Ok(data) => data,
+            Err(err) if err == SrError::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        let snapshot = data
+            .print_string(DataFormat::XML, DataPrinterFlags::WITH_SIBLINGS)
+            .map_err(|_| SrError::Ly)?;
+
+        Ok(Some(snapshot))
+    }
+
+    /// Restore running from the snapshot of the pending commit, which stays
+    /// pending if that fails.
+    fn rollback(&mut self) -> Result<(), SrErrorInfo> {
+        let Some(pending) = self.pending.as_ref() else {
+            return Ok(());
+        };
+        let ctx = self.session.get_context();
+        let data = match &pending.snapshot {
+            Some(snapshot) => Some(
+                DataTree::parse_string(
+                    &ctx,
+                    snapshot,
+                    DataFormat::XML,
+                    DataParserFlags::empty(),
+                    DataValidationFlags::NO_STATE,
+                )
+                .map_err(|_| SrError::Ly)?,
+            ),
+            None => None,
+        };
+
+        self.session.replace_config(data, None, None)?;
+        self.pending = None;
+
+        Ok(())
+    }
+
+    /// Get the pending commit, checking the persist ID.
+    fn pending(
+        &self,
+        persist_id: Option<&str>,
+    ) -> Result<&Pending, SrErrorInfo> {
+        let pending = self.pending.as_ref().ok_or_else(|| {
+            SrErrorInfo::new(
+                SrError::NotFound,
+                "No confirmed commit is pending.",
+            )
+        })?;
+        if pending.persist.as_deref() != persist_id {
+            return Err(SrErrorInfo::new(
+                SrError::InvalArg,
+                "Persist ID does not match the confirmed commit.",
+            ));
+        }
+
+        Ok(pending)
+    }
+}
+
+impl SrConfirmedCommit {
+    /// Create with a new running session of the connection.
+    pub fn new(connection: &SrConnection) -> Result<Self, SrError> {
+        let session = connection.new_session(SrDatastore::Running)?;
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                session,
+                pending: None,
+                shutdown: false,
+                detached: false,
+            }),
+            timer: Condvar::new(),
+        });
+
+        let timer = {
+            let shared = shared.clone();
+            thread::spawn(move || Self::run_timer(&shared))
+        };
+
+        Ok(Self {
+            shared,
+            timer: Some(timer),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.shared.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Roll back the pending commit when it expires.
+    fn run_timer(shared: &Shared) {
+        let mut state = shared.state.lock().unwrap_or_else(|e| e.into_inner());
+        while !state.shutdown {
+            let deadline = state
+                .pending
+                .as_ref()
+                .filter(|p| p.error.is_none())
+                .map(|p| p.deadline);
+            if state.detached && deadline.is_none() {
+                break;
+            }
+            state = match deadline {
+                Some(deadline) if deadline <= Instant::now() => {
+                    if let Err(err) = state.rollback() {
+                        if let Some(pending) = state.pending.as_mut() {
+                            pending.error = Some(err);
+                        }
+                    }
+                    state
+                }
+                Some(deadline) => {
+                    let timeout =
+                        deadline.saturating_duration_since(Instant::now());
+                    shared
+                        .timer
+                        .wait_timeout(state, timeout)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => {
+                    shared.timer.wait(state).unwrap_or_else(|e| e.into_inner())
+                }
+            };
+        }
+    }
+
+    /// Copy candidate to running, rolled back unless confirmed within the
+    /// timeout. With `persist` the commit can only be confirmed or cancelled
+    /// with it as persist ID. While a commit is pending, this extends it and
+    /// `persist_id` has to match its persist.
+    pub fn commit_confirmed(
+        &self,
+        timeout: Duration,
+        persist: Option<&str>,
+        persist_id: Option<&str>,
+    ) -> Result<(), SrErrorInfo> {
+        let mut state = self.lock();
+        let snapshot = if state.pending.is_some() {
+            state.pending(persist_id)?.snapshot.clone()
+        } else {
+            state.snapshot()?
+        };
+
+        state.session.copy_config(
+            SrDatastore::Candidate,
+            None,
+            Duration::ZERO,
+        )?;
+        state.pending = Some(Pending {
+            snapshot,
+            persist: persist.map(str::to_string),
+            deadline: Instant::now() + timeout,
+            error: None,
+        });
+        self.shared.timer.notify_one();
+
+        Ok(())
+    }
+
+    /// Confirm the pending commit.
+    pub fn confirm(&self, persist_id: Option<&str>) -> Result<(), SrErrorInfo> {
+        let mut state = self.lock();
+        state.pending(persist_id)?;
+        state.pending = None;
+        self.shared.timer.notify_one();
+
+        Ok(())
+    }
+
+    /// Cancel the pending commit, restoring running.
+    pub fn cancel(&self, persist_id: Option<&str>) -> Result<(), SrErrorInfo> {
+        let mut state = self.lock();
+        state.pending(persist_id)?;
+        let ret = state.rollback();
+        self.shared.timer.notify_one();
+
+        ret
+    }
+
+    /// Whether a commit waits for its confirmation.
+    pub fn is_pending(&self) -> bool {
+        self.lock().pending.is_some()
+    }
+
+    /// Error of the failed rollback of the expired commit, which stays
+    /// pending until it is cancelled or committed again.
+    pub fn rollback_error(&self) -> Option<SrErrorInfo> {
+        self.lock().pending.as_ref().and_then(|p| p.error.clone())
+    }
+
+    /// Roll back the pending commit, with a persist ID too, and stop the
+    /// timer.
+    pub fn close(mut self) -> Result<(), SrErrorInfo> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<(), SrErrorInfo> {
+        let ret = {
+            let mut state = self.lock();
+            let ret = state.rollback();
+            state.shutdown = true;
+            self.shared.timer.notify_one();
+            ret
+        };
+
+        if let Some(timer) = self.timer.take() {
+            let _ = timer.join();
+        }
+        ret
+    }
+}
+
+impl Drop for SrConfirmedCommit {
+    fn drop(&mut self) {
+        if self.timer.is_none() {
+            return;
+        }
+
+        // A persistent commit is left to the timer.
+        {
+            let mut state = self.lock();
+            if state.pending.as_ref().is_some_and(|p| p.persist.is_some()) {
+                state.detached = true;
+                self.shared.timer.notify_one();
+                return;
+            }
+        }
+        let _ = self.shutdown();
+    }
+}
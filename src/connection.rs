@@ -1,15 +1,19 @@
 use crate::common::str_to_cstring;
 use crate::enums::SrDatastore;
-use crate::errors::SrError;
+use crate::errors::{SrError, SrErrorInfo};
 use crate::session::{SrSession, SrSessionId};
 use libc::c_int;
 use std::collections::HashMap;
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
 use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use std::os::raw::c_char;
 use std::path::Path;
 use std::ptr;
+use std::sync::Arc;
 use sysrepo_sys as ffi_sys;
 use yang3::context::Context;
+use yang3::data::{Data, DataFormat, DataPrinterFlags, DataTree};
 use yang3::utils::Binding;
 
 pub enum ConnectionOptions {
@@ -21,14 +25,173 @@ pub enum ConnectionOptions {
         ffi_sys::sr_datastore_t_SR_DS_FACTORY_DEFAULT as isize,
 }
 
+/// Initial data of a module.
+pub enum SrInitialData<'a> {
+    /// File with the data, the format is detected from the extension.
+    File(&'a Path),
+    /// Data in the given format.
+    String(&'a str, DataFormat),
+    /// Data tree.
+    Tree(&'a DataTree<'a>),
+}
+
+/// Data, data path and format arguments of sysrepo.
+type RawInitialData = (Option<CString>, Option<CString>, ffi_sys::LYD_FORMAT);
+
+impl SrInitialData<'_> {
+    /// Convert into the data, data path and format arguments of sysrepo.
+    fn to_raw(&self) -> Result<RawInitialData, SrError> {
+        match self {
+            SrInitialData::File(file) => Ok((
+                None,
+                Some(path_to_cstring(file)?),
+                ffi_sys::LYD_FORMAT_LYD_UNKNOWN,
+            )),
+            SrInitialData::String(data, format) => Ok((
+                Some(str_to_cstring(data)?),
+                None,
+                *format as ffi_sys::LYD_FORMAT,
+            )),
+            SrInitialData::Tree(tree) => {
+                let data = tree
+                    .print_string(
+                        DataFormat::XML,
+                        DataPrinterFlags::WITH_SIBLINGS,
+                    )
+                    .map_err(|_| SrError::Ly)?;
+                Ok((
+                    Some(str_to_cstring(&data)?),
+                    None,
+                    DataFormat::XML as ffi_sys::LYD_FORMAT,
+                ))
+            }
+        }
+    }
+}
+
+/// Owner, group and permissions of a module datastore.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SrModuleAccess {
+    pub owner: String,
+    pub group: String,
+    pub perm: libc::mode_t,
+}
+
+/// Sysrepo connection context, disconnected when the last handle, session or
+/// subscription using it is dropped.
+pub(crate) struct RawConnection(*mut ffi_sys::sr_conn_ctx_t);
+
+unsafe impl Send for RawConnection {}
+unsafe impl Sync for RawConnection {}
+
+impl Drop for RawConnection {
+    fn drop(&mut self) {
+        unsafe {
+            ffi_sys::sr_disconnect(self.0);
+        }
+    }
+}
+
+/// Libyang context of a connection, acquired while the guard is alive.
+///
+/// Installing, updating or removing modules waits until every guard of the
+/// connection is dropped, so do not keep it longer than needed.
+#[must_use]
+pub struct ContextGuard {
+    conn: *mut ffi_sys::sr_conn_ctx_t,
+    raw: *const ffi_sys::ly_ctx,
+    ctx: ManuallyDrop<Context>,
+
+    /// Keeps the connection open while the context is used.
+    _connection: Option<Arc<RawConnection>>,
+}
+
+impl ContextGuard {
+    /// Acquire the context of the connection.
+    pub(crate) unsafe fn acquire(
+        conn: *mut ffi_sys::sr_conn_ctx_t,
+        connection: Option<Arc<RawConnection>>,
+    ) -> Self {
+        let raw = ffi_sys::sr_acquire_context(conn);
+        let ctx = raw as *mut libyang3_sys::ly_ctx;
+        Self {
+            conn,
+            raw,
+            ctx: ManuallyDrop::new(Context::from_raw(&(), ctx)),
+            _connection: connection,
+        }
+    }
+
+    /// Get raw libyang context.
+    pub(crate) fn raw(&self) -> *const ffi_sys::ly_ctx {
+        self.raw
+    }
+}
+
+/// Store all the libyang errors of this thread instead of only the last one,
+/// until dropped.
+pub(crate) struct LogStore {
+    ctx: *const ffi_sys::ly_ctx,
+    /// Options used by libyang while stored.
+    _options: Box<u32>,
+}
+
+impl LogStore {
+    pub(crate) unsafe fn new(ctx: *const ffi_sys::ly_ctx) -> Self {
+        let mut options = Box::new(ffi_sys::LY_LOLOG | ffi_sys::LY_LOSTORE);
+        ffi_sys::ly_temp_log_options(&mut *options);
+        ffi_sys::ly_err_clean(ctx as *mut _, ptr::null_mut());
+        Self {
+            ctx,
+            _options: options,
+        }
+    }
+}
+
+impl Drop for LogStore {
+    fn drop(&mut self) {
+        unsafe {
+            ffi_sys::ly_err_clean(self.ctx as *mut _, ptr::null_mut());
+            ffi_sys::ly_temp_log_options(ptr::null_mut());
+        }
+    }
+}
+
+impl Deref for ContextGuard {
+    type Target = Context;
+
+    fn deref(&self) -> &Self::Target {
+        &self.ctx
+    }
+}
+
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        unsafe {
+            ffi_sys::sr_release_context(self.conn);
+        }
+    }
+}
+
+/// Sysrepo connection. Clones share the same connection, which stays open
+/// while any clone or session started from it is alive.
 pub struct SrConnection {
-    raw_connection: *mut ffi_sys::sr_conn_ctx_t,
+    raw_connection: Arc<RawConnection>,
     sessions: HashMap<SrSessionId, SrSession>,
 }
 
 unsafe impl Send for SrConnection {}
 unsafe impl Sync for SrConnection {}
 
+impl Clone for SrConnection {
+    fn clone(&self) -> Self {
+        Self {
+            raw_connection: self.raw_connection.clone(),
+            sessions: HashMap::new(),
+        }
+    }
+}
+
 impl SrConnection {
     pub fn new(options: ConnectionOptions) -> Result<Self, SrError> {
         let mut conn = std::ptr::null_mut();
@@ -39,18 +202,16 @@ impl SrConnection {
             Err(SrError::from(rc))
         } else {
             Ok(Self {
-                raw_connection: conn,
+                raw_connection: Arc::new(RawConnection(conn)),
                 sessions: HashMap::new(),
             })
         }
     }
 
-    /// Disconnect.
-    pub fn disconnect(&mut self) {
-        unsafe {
-            ffi_sys::sr_disconnect(self.raw_connection);
-        }
-        self.raw_connection = std::ptr::null_mut();
+    /// Disconnect, stopping the sessions kept by this handle. The connection
+    /// is closed once no other clone or session uses it.
+    pub fn disconnect(mut self) {
+        self.sessions.clear();
     }
 
     /// Add session to map.
@@ -70,27 +231,97 @@ impl SrConnection {
         &mut self,
         ds: SrDatastore,
     ) -> Result<&mut SrSession, SrError> {
+        let sess = self.new_session(ds)?;
+        let id = unsafe { sess.get_raw() };
+        self.insert_session(id, sess);
+        Ok(self.sessions.get_mut(&id).unwrap())
+    }
+
+    /// Start a session owned by the caller. It keeps the connection open
+    /// and can be moved to another thread.
+    pub fn new_session(&self, ds: SrDatastore) -> Result<SrSession, SrError> {
         let mut sess = std::ptr::null_mut();
         let rc = unsafe {
-            ffi_sys::sr_session_start(self.raw_connection, ds as u32, &mut sess)
+            ffi_sys::sr_session_start(self.raw(), ds as u32, &mut sess)
         };
         if rc != SrError::Ok as i32 {
             Err(SrError::from(rc))
         } else {
-            let id = sess;
-            self.insert_session(id, SrSession::from(sess, true));
-            Ok(self.sessions.get_mut(&(id as SrSessionId)).unwrap())
+            Ok(SrSession::from_connection(
+                sess,
+                self.raw_connection.clone(),
+            ))
         }
     }
 
-    /// Returns the libyang3 context associated with this Session
-    pub fn get_context(&self) -> ManuallyDrop<Context> {
-        let ctx = unsafe {
-            let ctx = ffi_sys::sr_acquire_context(self.raw_connection)
-                as *mut libyang3_sys::ly_ctx;
-            Context::from_raw(&(), ctx)
-        };
-        ManuallyDrop::new(ctx)
+    /// Get raw connection context.
+    pub unsafe fn get_raw_mut(&self) -> *mut ffi_sys::sr_conn_ctx_t {
+        self.raw()
+    }
+
+    fn raw(&self) -> *mut ffi_sys::sr_conn_ctx_t {
+        self.raw_connection.0
+    }
+
+    /// Acquire the libyang3 context of the connection, released when the
+    /// guard is dropped.
+    pub fn get_context(&self) -> ContextGuard {
+        unsafe {
+            ContextGuard::acquire(self.raw(), Some(self.raw_connection.clone()))
+        }
+    }
+
+    /// Validate a data tree against the modules installed in sysrepo, as
+    /// data of the datastore, without touching any datastore. All the
+    /// validation errors are returned.
+    pub fn validate_tree(
+        &self,
+        tree: &DataTree<'_>,
+        ds: SrDatastore,
+    ) -> Result<(), SrErrorInfo> {
+        let options = match ds {
+            SrDatastore::Operational => ffi_sys::LYD_VALIDATE_OPERATIONAL,
+            _ => ffi_sys::LYD_VALIDATE_NO_STATE,
+        } | ffi_sys::LYD_VALIDATE_MULTI_ERROR;
+
+        let guard = self.get_context();
+        let ctx = guard.raw();
+        unsafe {
+            let _log = LogStore::new(ctx);
+
+            // Work on a copy in the sysrepo context.
+            let mut dup = ptr::null_mut();
+            let mut ret = match tree.raw().is_null() {
+                true => ffi_sys::LY_ERR_LY_SUCCESS,
+                false => ffi_sys::lyd_dup_siblings_to_ctx(
+                    tree.raw(),
+                    ctx,
+                    ptr::null_mut(),
+                    ffi_sys::LYD_DUP_RECURSIVE,
+                    &mut dup,
+                ),
+            };
+            if ret == ffi_sys::LY_ERR_LY_SUCCESS {
+                ret = ffi_sys::lyd_validate_all(
+                    &mut dup,
+                    ctx,
+                    options,
+                    ptr::null_mut(),
+                );
+            }
+
+            let result = match ret {
+                ffi_sys::LY_ERR_LY_SUCCESS => Ok(()),
+                _ => Err(SrErrorInfo::from_context(
+                    ctx,
+                    SrError::ValidationFailed,
+                )),
+            };
+
+            ffi_sys::lyd_free_all(dup);
+
+            result
+        }
     }
 
     pub fn install_module(
@@ -130,10 +361,83 @@ impl SrConnection {
 
         let ret = unsafe {
             ffi_sys::sr_install_module(
-                self.raw_connection,
+                self.raw(),
+                path.as_ptr(),
+                search_dirs,
+                features_ptr.as_mut_ptr(),
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Install a module with initial data for all its datastores.
+    pub fn install_module_with_data(
+        &self,
+        file: &Path,
+        search_dirs: Option<&str>,
+        features: Option<&[&str]>,
+        data: SrInitialData<'_>,
+    ) -> Result<(), SrError> {
+        let path = path_to_cstring(file)?;
+        let search_dirs = match search_dirs {
+            None => None,
+            Some(dirs) => Some(str_to_cstring(dirs)?),
+        };
+        let search_dirs =
+            search_dirs.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+
+        let features = features
+            .unwrap_or_default()
+            .iter()
+            .map(|x| str_to_cstring(x))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut features_ptr =
+            features.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
+        features_ptr.push(ptr::null());
+
+        let (data, data_path, format) = data.to_raw()?;
+
+        let ret = unsafe {
+            ffi_sys::sr_install_module2(
+                self.raw(),
                 path.as_ptr(),
                 search_dirs,
                 features_ptr.as_mut_ptr(),
+                ptr::null(),
+                ptr::null(),
+                ptr::null(),
+                0,
+                data.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                data_path.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                format,
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Set the factory-default data of installed modules.
+    pub fn install_factory_config(
+        &self,
+        data: SrInitialData<'_>,
+    ) -> Result<(), SrError> {
+        let (data, data_path, format) = data.to_raw()?;
+
+        let ret = unsafe {
+            ffi_sys::sr_install_factory_config(
+                self.raw(),
+                data.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                data_path.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                format,
             )
         };
 
@@ -144,6 +448,275 @@ impl SrConnection {
         Ok(())
     }
 
+    /// Install several modules at once, each with its enabled features.
+    pub fn install_modules(
+        &self,
+        modules: &[(&Path, Option<&[&str]>)],
+        search_dirs: Option<&str>,
+    ) -> Result<(), SrError> {
+        let paths = modules
+            .iter()
+            .map(|(file, _)| path_to_cstring(file))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut paths_ptr =
+            paths.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
+        paths_ptr.push(ptr::null());
+
+        let search_dirs = match search_dirs {
+            None => None,
+            Some(dirs) => Some(str_to_cstring(dirs)?),
+        };
+        let search_dirs =
+            search_dirs.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+
+        let features = modules
+            .iter()
+            .map(|(_, features)| {
+                features
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|x| str_to_cstring(x))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut features_ptr = features
+            .iter()
+            .map(|features| {
+                let mut ptrs =
+                    features.iter().map(|x| x.as_ptr()).collect::<Vec<_>>();
+                ptrs.push(ptr::null());
+                ptrs
+            })
+            .collect::<Vec<_>>();
+        let mut features_list = features_ptr
+            .iter_mut()
+            .map(|x| x.as_mut_ptr())
+            .collect::<Vec<_>>();
+
+        let ret = unsafe {
+            ffi_sys::sr_install_modules(
+                self.raw(),
+                paths_ptr.as_mut_ptr(),
+                search_dirs,
+                features_list.as_mut_ptr(),
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Update an installed module to the newer revision in `file`.
+    pub fn update_module(
+        &self,
+        file: &Path,
+        search_dirs: Option<&str>,
+    ) -> Result<(), SrError> {
+        let path = path_to_cstring(file)?;
+        let search_dirs = match search_dirs {
+            None => None,
+            Some(dirs) => Some(str_to_cstring(dirs)?),
+        };
+        let search_dirs =
+            search_dirs.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+
+        let ret = unsafe {
+            ffi_sys::sr_update_module(self.raw(), path.as_ptr(), search_dirs)
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Enable a feature of an installed module.
+    pub fn enable_module_feature(
+        &self,
+        module_name: &str,
+        feature_name: &str,
+    ) -> Result<(), SrError> {
+        let module_name = str_to_cstring(module_name)?;
+        let feature_name = str_to_cstring(feature_name)?;
+
+        let ret = unsafe {
+            ffi_sys::sr_enable_module_feature(
+                self.raw(),
+                module_name.as_ptr(),
+                feature_name.as_ptr(),
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Disable a feature of an installed module.
+    pub fn disable_module_feature(
+        &self,
+        module_name: &str,
+        feature_name: &str,
+    ) -> Result<(), SrError> {
+        let module_name = str_to_cstring(module_name)?;
+        let feature_name = str_to_cstring(feature_name)?;
+
+        let ret = unsafe {
+            ffi_sys::sr_disable_module_feature(
+                self.raw(),
+                module_name.as_ptr(),
+                feature_name.as_ptr(),
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Enable or disable storing notifications of a module for replay,
+    /// `None` changes all the modules.
+    pub fn set_module_replay_support(
+        &self,
+        module_name: Option<&str>,
+        enable: bool,
+    ) -> Result<(), SrError> {
+        let module_name = match module_name {
+            None => None,
+            Some(name) => Some(str_to_cstring(name)?),
+        };
+        let module_name =
+            module_name.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+
+        let ret = unsafe {
+            ffi_sys::sr_set_module_replay_support(
+                self.raw(),
+                module_name,
+                enable as c_int,
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Check whether notification replay is enabled for a module.
+    pub fn module_replay_support(
+        &self,
+        module_name: &str,
+    ) -> Result<bool, SrError> {
+        let module_name = str_to_cstring(module_name)?;
+        let mut earliest_notif =
+            unsafe { std::mem::zeroed::<ffi_sys::timespec>() };
+        let mut enabled: c_int = 0;
+
+        let ret = unsafe {
+            ffi_sys::sr_get_module_replay_support(
+                self.raw(),
+                module_name.as_ptr(),
+                &mut earliest_notif,
+                &mut enabled,
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(enabled != 0)
+    }
+
+    /// Set the owner, group and permissions of a module datastore. `None`
+    /// keeps the current value.
+    pub fn set_module_ds_access(
+        &self,
+        module_name: &str,
+        datastore: SrDatastore,
+        owner: Option<&str>,
+        group: Option<&str>,
+        perm: Option<libc::mode_t>,
+    ) -> Result<(), SrError> {
+        let module_name = str_to_cstring(module_name)?;
+        let owner = match owner {
+            None => None,
+            Some(owner) => Some(str_to_cstring(owner)?),
+        };
+        let group = match group {
+            None => None,
+            Some(group) => Some(str_to_cstring(group)?),
+        };
+
+        let ret = unsafe {
+            ffi_sys::sr_set_module_ds_access(
+                self.raw(),
+                module_name.as_ptr(),
+                datastore as c_int,
+                owner.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                group.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                perm.unwrap_or(libc::mode_t::MAX),
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        Ok(())
+    }
+
+    /// Get the owner, group and permissions of a module datastore.
+    pub fn get_module_ds_access(
+        &self,
+        module_name: &str,
+        datastore: SrDatastore,
+    ) -> Result<SrModuleAccess, SrError> {
+        let module_name = str_to_cstring(module_name)?;
+        let mut owner: *mut c_char = ptr::null_mut();
+        let mut group: *mut c_char = ptr::null_mut();
+        let mut perm: libc::mode_t = 0;
+
+        let ret = unsafe {
+            ffi_sys::sr_get_module_ds_access(
+                self.raw(),
+                module_name.as_ptr(),
+                datastore as c_int,
+                &mut owner,
+                &mut group,
+                &mut perm,
+            )
+        };
+
+        if ret != SrError::Ok as i32 {
+            return Err(SrError::from(ret));
+        }
+
+        let take = |s: *mut c_char| unsafe {
+            if s.is_null() {
+                return String::new();
+            }
+            let string = CStr::from_ptr(s).to_string_lossy().into_owned();
+            libc::free(s as *mut libc::c_void);
+            string
+        };
+
+        Ok(SrModuleAccess {
+            owner: take(owner),
+            group: take(group),
+            perm,
+        })
+    }
+
     pub fn remove_module(
         &self,
         module_name: &str,
@@ -157,7 +730,7 @@ impl SrConnection {
         };
 
         let ret = unsafe {
-            ffi_sys::sr_remove_module(self.raw_connection, path.as_ptr(), force)
+            ffi_sys::sr_remove_module(self.raw(), path.as_ptr(), force)
         };
 
         if ret != SrError::Ok as i32 {
@@ -168,6 +741,13 @@ impl SrConnection {
     }
 }
 
+fn path_to_cstring(file: &Path) -> Result<CString, SrError> {
+    match file.to_str() {
+        None => Err(SrError::InvalArg),
+        Some(path) => str_to_cstring(path),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,4 +777,13 @@ mod tests {
 
         assert!(true)
     }
+
+    #[test]
+    fn path_to_cstring_rejects_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"./assets/yang/\xff.yang"));
+        assert_eq!(path_to_cstring(path).unwrap_err(), SrError::InvalArg);
+    }
 }
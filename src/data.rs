@@ -0,0 +1,84 @@
+//! Data trees returned by sysrepo.
+
+use crate::errors::SrError;
+use std::mem::ManuallyDrop;
+use std::ops::Deref;
+use sysrepo_sys as ffi_sys;
+use yang3::context::Context;
+use yang3::data::{Data, DataTree};
+use yang3::utils::Binding;
+
+/// Data returned by sysrepo. It holds the context of the connection
+/// acquired until dropped, when the data are released.
+pub struct SrData<'a> {
+    raw: *mut ffi_sys::sr_data_t,
+    /// Tree owned by `raw`.
+    tree: ManuallyDrop<DataTree<'a>>,
+}
+
+impl<'a> SrData<'a> {
+    /// Take ownership of the data returned by sysrepo, which must not be
+    /// null.
+    pub(crate) unsafe fn from_raw(
+        context: &'a Context,
+        raw: *mut ffi_sys::sr_data_t,
+    ) -> Self {
+        let tree = DataTree::from_raw(context, (*raw).tree);
+        Self {
+            raw,
+            tree: ManuallyDrop::new(tree),
+        }
+    }
+
+    /// Duplicate the tree so that it outlives the data.
+    pub fn into_owned(self) -> Result<DataTree<'a>, SrError> {
+        if self.tree.raw().is_null() {
+            return Ok(DataTree::new(self.tree.context()));
+        }
+
+        self.tree.duplicate().map_err(|_| SrError::Ly)
+    }
+}
+
+impl<'a> Deref for SrData<'a> {
+    type Target = DataTree<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl Drop for SrData<'_> {
+    fn drop(&mut self) {
+        // Frees the tree and releases the context.
+        unsafe {
+            ffi_sys::sr_release_data(self.raw);
+        }
+    }
+}
+
+/// Data tree owned by sysrepo and only borrowed, e.g. the changes of an
+/// event. It is never freed through this handle.
+pub struct SrDataRef<'a> {
+    tree: ManuallyDrop<DataTree<'a>>,
+}
+
+impl<'a> SrDataRef<'a> {
+    /// Borrow the tree, which must stay valid for `'a`.
+    pub(crate) unsafe fn from_raw(
+        context: &'a Context,
+        raw: *mut libyang3_sys::lyd_node,
+    ) -> Self {
+        Self {
+            tree: ManuallyDrop::new(DataTree::from_raw(context, raw)),
+        }
+    }
+}
+
+impl<'a> Deref for SrDataRef<'a> {
+    type Target = DataTree<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
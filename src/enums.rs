@@ -39,6 +39,18 @@ pub enum SrLogLevel {
     Debug = ffi_sys::sr_log_level_t_SR_LL_DBG as isize,
 }
 
+impl From<ffi_sys::sr_log_level_t> for SrLogLevel {
+    fn from(value: ffi_sys::sr_log_level_t) -> Self {
+        match value {
+            ffi_sys::sr_log_level_t_SR_LL_ERR => Self::Error,
+            ffi_sys::sr_log_level_t_SR_LL_WRN => Self::Warn,
+            ffi_sys::sr_log_level_t_SR_LL_INF => Self::Info,
+            ffi_sys::sr_log_level_t_SR_LL_DBG => Self::Debug,
+            _ => Self::None,
+        }
+    }
+}
+
 /// Conn Flag.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum SrConnFlag {
@@ -53,6 +65,7 @@ pub enum SrDatastore {
     Running = ffi_sys::sr_datastore_t_SR_DS_RUNNING as isize,
     Candidate = ffi_sys::sr_datastore_t_SR_DS_CANDIDATE as isize,
     Operational = ffi_sys::sr_datastore_t_SR_DS_OPERATIONAL as isize,
+    FactoryDefault = ffi_sys::sr_datastore_t_SR_DS_FACTORY_DEFAULT as isize,
 }
 
 impl From<ffi_sys::sr_datastore_t> for SrDatastore {
@@ -62,6 +75,9 @@ impl From<ffi_sys::sr_datastore_t> for SrDatastore {
             ffi_sys::sr_datastore_t_SR_DS_RUNNING => Self::Running,
             ffi_sys::sr_datastore_t_SR_DS_CANDIDATE => Self::Candidate,
             ffi_sys::sr_datastore_t_SR_DS_OPERATIONAL => Self::Operational,
+            ffi_sys::sr_datastore_t_SR_DS_FACTORY_DEFAULT => {
+                Self::FactoryDefault
+            }
             _ => panic!("DS not found"),
         }
     }
@@ -106,6 +122,7 @@ pub enum SrSubcribeFlag {
     Enabled = ffi_sys::sr_subscr_flag_t_SR_SUBSCR_ENABLED as isize,
     Update = ffi_sys::sr_subscr_flag_t_SR_SUBSCR_UPDATE as isize,
     OperMerge = ffi_sys::sr_subscr_flag_t_SR_SUBSCR_OPER_MERGE as isize,
+    OperPollDiff = ffi_sys::sr_subscr_flag_t_SR_SUBSCR_OPER_POLL_DIFF as isize,
 }
 
 /// Notification Type.
@@ -1,7 +1,13 @@
 use libc::c_int;
+use std::ffi::CStr;
 use std::fmt::{Display, Formatter};
+use std::os::raw::c_char;
+use std::ptr;
 use sysrepo_sys as ffi_sys;
 
+/// Error format of errors carrying NETCONF error fields.
+pub const NETCONF_FORMAT: &str = "NETCONF";
+
 /// Error.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum SrError {
@@ -75,3 +81,306 @@ impl From<c_int> for SrError {
         }
     }
 }
+
+impl std::error::Error for SrError {}
+
+/// NETCONF error fields, as defined by RFC 6241 `rpc-error`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NetconfError {
+    pub error_type: String,
+    pub error_tag: String,
+    pub error_app_tag: Option<String>,
+    pub error_path: Option<String>,
+    pub error_message: String,
+    /// `error-info` elements and their values.
+    pub error_info: Vec<(String, String)>,
+}
+
+impl NetconfError {
+    unsafe fn from_raw(err: &ffi_sys::sr_error_info_err_t) -> Option<Self> {
+        let mut error_type = ptr::null();
+        let mut error_tag = ptr::null();
+        let mut error_app_tag = ptr::null();
+        let mut error_path = ptr::null();
+        let mut error_message = ptr::null();
+        let mut elements = ptr::null_mut();
+        let mut values = ptr::null_mut();
+        let mut count: u32 = 0;
+
+        let rc = ffi_sys::sr_err_get_netconf_error(
+            err,
+            &mut error_type,
+            &mut error_tag,
+            &mut error_app_tag,
+            &mut error_path,
+            &mut error_message,
+            &mut elements,
+            &mut values,
+            &mut count,
+        );
+        if rc != SrError::Ok as c_int {
+            return None;
+        }
+
+        let error_info = (0..count as usize)
+            .map(|i| {
+                (
+                    cstr_to_string(*elements.add(i)).unwrap_or_default(),
+                    cstr_to_string(*values.add(i)).unwrap_or_default(),
+                )
+            })
+            .collect();
+        libc::free(elements as *mut _);
+        libc::free(values as *mut _);
+
+        Some(Self {
+            error_type: cstr_to_string(error_type).unwrap_or_default(),
+            error_tag: cstr_to_string(error_tag).unwrap_or_default(),
+            error_app_tag: cstr_to_string(error_app_tag)
+                .filter(|tag| !tag.is_empty()),
+            error_path: cstr_to_string(error_path)
+                .filter(|path| !path.is_empty()),
+            error_message: cstr_to_string(error_message).unwrap_or_default(),
+            error_info,
+        })
+    }
+}
+
+unsafe fn cstr_to_string(s: *const c_char) -> Option<String> {
+    match s.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(s).to_string_lossy().into_owned()),
+    }
+}
+
+/// Single error reported by sysrepo.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SrErrorItem {
+    pub code: SrError,
+    pub message: String,
+    /// Data or schema path the error refers to, if sysrepo or libyang
+    /// reported one.
+    pub path: Option<String>,
+    /// Format of the additional error data, e.g. "NETCONF".
+    pub format: Option<String>,
+    /// NETCONF error fields, if the error format is "NETCONF".
+    pub netconf: Option<NetconfError>,
+}
+
+impl SrErrorItem {
+    unsafe fn from_raw(err: &ffi_sys::sr_error_info_err_t) -> Self {
+        let message = cstr_to_string(err.message).unwrap_or_default();
+        let format = cstr_to_string(err.error_format);
+        let netconf = match format.as_deref() {
+            Some(NETCONF_FORMAT) => NetconfError::from_raw(err),
+            _ => None,
+        };
+        let path = netconf
+            .as_ref()
+            .and_then(|netconf| netconf.error_path.clone());
+
+        Self {
+            code: SrError::from(err.err_code as c_int),
+            message,
+            path,
+            format,
+            netconf,
+        }
+    }
+}
+
+impl Display for SrErrorItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.message.is_empty() {
+            true => write!(f, "{}", self.code),
+            false => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Error with all the details sysrepo collected for a failed call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SrErrorInfo {
+    code: SrError,
+    errors: Vec<SrErrorItem>,
+}
+
+impl SrErrorInfo {
+    pub fn new(code: SrError, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            errors: vec![SrErrorItem {
+                code,
+                message: message.into(),
+                path: None,
+                format: None,
+                netconf: None,
+            }],
+        }
+    }
+
+    /// Error carrying NETCONF error fields, e.g. to be returned from a
+    /// callback to the NETCONF client.
+    pub fn netconf(code: SrError, netconf: NetconfError) -> Self {
+        Self {
+            code,
+            errors: vec![SrErrorItem {
+                code,
+                message: netconf.error_message.clone(),
+                path: netconf.error_path.clone(),
+                format: Some(NETCONF_FORMAT.to_string()),
+                netconf: Some(netconf),
+            }],
+        }
+    }
+
+    /// Collect the errors stored in the session by the last failed call.
+    pub unsafe fn from_session(
+        sess: *mut ffi_sys::sr_session_ctx_t,
+        code: SrError,
+    ) -> Self {
+        let mut err_info: *const ffi_sys::sr_error_info_t = ptr::null();
+        let rc = ffi_sys::sr_session_get_error(sess, &mut err_info);
+
+        let errors = match rc != SrError::Ok as c_int || err_info.is_null() {
+            true => Vec::new(),
+            false => {
+                let err_info = &*err_info;
+                match err_info.err.is_null() {
+                    true => Vec::new(),
+                    false => std::slice::from_raw_parts(
+                        err_info.err,
+                        err_info.err_count as usize,
+                    )
+                    .iter()
+                    .map(|err| SrErrorItem::from_raw(err))
+                    .collect(),
+                }
+            }
+        };
+
+        let mut info = Self { code, errors };
+        info.ly_paths(sess);
+        info
+    }
+
+    /// Take the paths of the errors from the libyang errors of this thread,
+    /// sysrepo only passes on their messages.
+    unsafe fn ly_paths(&mut self, sess: *mut ffi_sys::sr_session_ctx_t) {
+        if self.errors.iter().all(|err| err.path.is_some()) {
+            return;
+        }
+
+        let conn = ffi_sys::sr_session_get_connection(sess);
+        let ctx = ffi_sys::sr_acquire_context(conn);
+        let ly_errors = Self::from_context(ctx, self.code).errors;
+        ffi_sys::sr_release_context(conn);
+
+        for err in self.errors.iter_mut().filter(|err| err.path.is_none()) {
+            err.path = ly_errors
+                .iter()
+                .filter(|ly_err| !ly_err.message.is_empty())
+                .find(|ly_err| err.message.contains(&ly_err.message))
+                .and_then(|ly_err| ly_err.path.clone());
+        }
+    }
+
+    /// Collect the libyang errors stored in the context for this thread.
+    pub(crate) unsafe fn from_context(
+        ctx: *const ffi_sys::ly_ctx,
+        code: SrError,
+    ) -> Self {
+        let mut errors = Vec::new();
+        let mut err = ffi_sys::ly_err_first(ctx);
+        while !err.is_null() {
+            let message = cstr_to_string((*err).msg).unwrap_or_default();
+            let path = cstr_to_string((*err).data_path)
+                .or_else(|| cstr_to_string((*err).schema_path));
+            errors.push(SrErrorItem {
+                code: SrError::Ly,
+                message,
+                path,
+                format: None,
+                netconf: None,
+            });
+            err = (*err).next;
+        }
+
+        Self { code, errors }
+    }
+
+    /// Error code of the failed call.
+    pub fn code(&self) -> SrError {
+        self.code
+    }
+
+    /// All errors reported for the failed call, in order.
+    pub fn errors(&self) -> &[SrErrorItem] {
+        &self.errors
+    }
+
+    /// Message of the first error.
+    pub fn message(&self) -> Option<&str> {
+        self.errors.first().map(|err| err.message.as_str())
+    }
+
+    /// NETCONF fields of the first error that has them.
+    pub fn netconf_error(&self) -> Option<&NetconfError> {
+        self.errors.iter().find_map(|err| err.netconf.as_ref())
+    }
+
+    /// Path of the first error that has one.
+    pub fn path(&self) -> Option<&str> {
+        self.errors.iter().find_map(|err| err.path.as_deref())
+    }
+}
+
+impl From<SrError> for SrErrorInfo {
+    fn from(code: SrError) -> Self {
+        Self {
+            code,
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl From<SrErrorInfo> for SrError {
+    fn from(err: SrErrorInfo) -> Self {
+        err.code
+    }
+}
+
+impl PartialEq<SrError> for SrErrorInfo {
+    fn eq(&self, other: &SrError) -> bool {
+        self.code == *other
+    }
+}
+
+impl Display for SrErrorInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code)?;
+        for err in &self.errors {
+            write!(f, ": {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SrErrorInfo {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_info_compares_with_code() {
+        let err = SrErrorInfo::from(SrError::NotFound);
+        assert_eq!(err, SrError::NotFound);
+        assert!(err.errors().is_empty());
+        assert_eq!(err.to_string(), "Not Found");
+    }
+}
@@ -2,10 +2,17 @@
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 
+#[cfg(feature = "tokio")]
+pub mod async_subscription;
 pub mod common;
+pub mod confirmed_commit;
 pub mod connection;
+pub mod data;
 pub mod enums;
 pub mod errors;
+#[cfg(feature = "tracing")]
+pub mod logging;
+pub mod nacm;
 pub mod session;
 pub mod subscription;
 pub mod value;
@@ -0,0 +1,129 @@
+//! Sysrepo and libyang logs as `tracing` events.
+//!
+//! Sysrepo messages use the `sysrepo` target, libyang messages the `libyang`
+//! target with the data or schema path in the `path` field. The events go to
+//! the subscriber that was the default when [`log_tracing`] was called, also
+//! when sysrepo logs from its own threads.
+
+use crate::enums::SrLogLevel;
+use libc::c_char;
+use std::ffi::CStr;
+use std::panic;
+use std::sync::RwLock;
+use sysrepo_sys as ffi_sys;
+use tracing::{Dispatch, Level};
+
+/// Dispatcher the callbacks log to, with the maximum level to log.
+static DISPATCH: RwLock<Option<(Dispatch, SrLogLevel)>> = RwLock::new(None);
+
+/// Log sysrepo and libyang messages up to the level as `tracing` events.
+pub fn log_tracing(log_level: SrLogLevel) {
+    let dispatch =
+        tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+    let ly_level = match log_level {
+        SrLogLevel::None | SrLogLevel::Error => ffi_sys::LY_LOG_LEVEL_LY_LLERR,
+        SrLogLevel::Warn => ffi_sys::LY_LOG_LEVEL_LY_LLWRN,
+        SrLogLevel::Info => ffi_sys::LY_LOG_LEVEL_LY_LLVRB,
+        SrLogLevel::Debug => ffi_sys::LY_LOG_LEVEL_LY_LLDBG,
+    };
+    *DISPATCH.write().unwrap() = Some((dispatch, log_level));
+
+    unsafe {
+        ffi_sys::sr_log_set_cb(Some(sr_log_callback));
+        ffi_sys::ly_set_log_clb(Some(ly_log_callback));
+        ffi_sys::ly_log_level(ly_level);
+    }
+}
+
+/// Stop logging to `tracing`.
+pub fn log_tracing_stop() {
+    unsafe {
+        ffi_sys::sr_log_set_cb(None);
+        ffi_sys::ly_set_log_clb(None);
+    }
+    *DISPATCH.write().unwrap() = None;
+}
+
+impl SrLogLevel {
+    /// Matching `tracing` level, `None` for `SrLogLevel::None`.
+    pub fn tracing_level(&self) -> Option<Level> {
+        match self {
+            SrLogLevel::None => None,
+            SrLogLevel::Error => Some(Level::ERROR),
+            SrLogLevel::Warn => Some(Level::WARN),
+            SrLogLevel::Info => Some(Level::INFO),
+            SrLogLevel::Debug => Some(Level::DEBUG),
+        }
+    }
+}
+
+/// Emit an event with a level only known at runtime.
+macro_rules! event {
+    ($target:literal, $level:expr, $($field:tt)*) => {
+        match $level {
+            SrLogLevel::None => {}
+            SrLogLevel::Error => {
+                tracing::event!(target: $target, Level::ERROR, $($field)*)
+            }
+            SrLogLevel::Warn => {
+                tracing::event!(target: $target, Level::WARN, $($field)*)
+            }
+            SrLogLevel::Info => {
+                tracing::event!(target: $target, Level::INFO, $($field)*)
+            }
+            SrLogLevel::Debug => {
+                tracing::event!(target: $target, Level::DEBUG, $($field)*)
+            }
+        }
+    };
+}
+
+/// Run the closure with the installed dispatcher if the level is logged,
+/// never unwinding into C.
+fn dispatch<F: FnOnce()>(level: &SrLogLevel, f: F) {
+    let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        let guard = DISPATCH.read().unwrap_or_else(|e| e.into_inner());
+        if let Some((dispatch, max_level)) = guard.as_ref() {
+            if level <= max_level {
+                tracing::dispatcher::with_default(dispatch, f);
+            }
+        }
+    }));
+}
+
+unsafe fn opt_str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok()
+    }
+}
+
+unsafe extern "C" fn sr_log_callback(
+    level: ffi_sys::sr_log_level_t,
+    message: *const c_char,
+) {
+    let level = SrLogLevel::from(level);
+    let message = opt_str(message).unwrap_or_default();
+
+    dispatch(&level, || event!("sysrepo", level, message));
+}
+
+unsafe extern "C" fn ly_log_callback(
+    level: ffi_sys::LY_LOG_LEVEL,
+    message: *const c_char,
+    data_path: *const c_char,
+    schema_path: *const c_char,
+    _line: u64,
+) {
+    let level = match level {
+        ffi_sys::LY_LOG_LEVEL_LY_LLERR => SrLogLevel::Error,
+        ffi_sys::LY_LOG_LEVEL_LY_LLWRN => SrLogLevel::Warn,
+        ffi_sys::LY_LOG_LEVEL_LY_LLVRB => SrLogLevel::Info,
+        _ => SrLogLevel::Debug,
+    };
+    let message = opt_str(message).unwrap_or_default();
+    let path = opt_str(data_path).or(opt_str(schema_path));
+
+    dispatch(&level, || event!("libyang", level, message, path));
+}
@@ -0,0 +1,152 @@
+//! NETCONF Access Control Model.
+//!
+//! Once NACM is initialized with [`SrSession::nacm_init`], every session with
+//! a NACM user set has its reads filtered and its operations checked against
+//! the `ietf-netconf-acm` configuration in the running datastore.
+
+use crate::common::str_to_cstring;
+use crate::connection::ContextGuard;
+use crate::errors::{SrError, SrErrorInfo};
+use crate::session::SrSession;
+use crate::subscription::SrSubscription;
+use std::ffi::CStr;
+use std::ptr;
+use sysrepo_sys as ffi_sys;
+use yang3::data::{Data, DataTree};
+
+/// Initialized NACM, destroyed on drop.
+pub struct SrNacm {
+    /// Subscription to the `ietf-netconf-acm` configuration.
+    subscription: Option<SrSubscription>,
+}
+
+impl Drop for SrNacm {
+    fn drop(&mut self) {
+        // Unsubscribe before the NACM data are freed.
+        self.subscription.take();
+
+        unsafe {
+            ffi_sys::sr_nacm_destroy();
+        }
+    }
+}
+
+/// Global NACM statistics.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SrNacmStats {
+    pub denied_operations: u32,
+    pub denied_data_writes: u32,
+    pub denied_notifications: u32,
+}
+
+impl SrSession {
+    /// Initialize NACM, subscribing to its configuration with this session.
+    pub fn nacm_init(
+        &self,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<SrNacm, SrErrorInfo> {
+        let mut subscription = ptr::null_mut();
+        let rc = unsafe {
+            ffi_sys::sr_nacm_init(self.get_raw_mut(), opts, &mut subscription)
+        };
+
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
+        } else {
+            let mut subscription = SrSubscription::from(subscription);
+            subscription.keep_session(self);
+            Ok(SrNacm {
+                subscription: Some(subscription),
+            })
+        }
+    }
+
+    /// Get global NACM statistics.
+    pub fn nacm_glob_stats(&self) -> Result<SrNacmStats, SrErrorInfo> {
+        let mut stats = SrNacmStats::default();
+        let rc = unsafe {
+            ffi_sys::sr_nacm_glob_stats(
+                self.get_raw_mut(),
+                &mut stats.denied_operations,
+                &mut stats.denied_data_writes,
+                &mut stats.denied_notifications,
+            )
+        };
+
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
+        } else {
+            Ok(stats)
+        }
+    }
+
+    /// Set the NACM user of this session, `None` stops applying NACM.
+    pub fn set_nacm_user(
+        &mut self,
+        user: Option<&str>,
+    ) -> Result<(), SrErrorInfo> {
+        let user = match user {
+            Some(user) => Some(str_to_cstring(user)?),
+            None => None,
+        };
+
+        let rc = unsafe {
+            ffi_sys::sr_nacm_set_user(
+                self.get_raw_mut(),
+                user.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+            )
+        };
+
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the NACM user of this session.
+    pub fn nacm_user(&self) -> Option<String> {
+        let user = unsafe { ffi_sys::sr_nacm_get_user(self.get_raw_mut()) };
+        if user.is_null() {
+            return None;
+        }
+
+        unsafe { Some(CStr::from_ptr(user).to_string_lossy().into_owned()) }
+    }
+
+    /// Check whether the NACM user may execute the operation node (RPC,
+    /// action or notification), fails with `SrError::Unauthorized`.
+    pub fn nacm_check_operation<'a>(
+        &self,
+        operation: &impl Data<'a>,
+    ) -> Result<(), SrErrorInfo> {
+        let rc = unsafe {
+            ffi_sys::sr_nacm_check_operation(
+                self.get_raw_mut(),
+                operation.raw(),
+            )
+        };
+
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check whether the NACM user may execute the operation at the path.
+    pub fn nacm_check_path(
+        &self,
+        context: &ContextGuard,
+        path: &str,
+    ) -> Result<(), SrErrorInfo> {
+        let mut operation = DataTree::new(context);
+        operation
+            .new_path(path, None, false)
+            .map_err(|_| SrError::Ly)?;
+        // Actions and notifications in data are below their parents.
+        let node = operation.find_path(path).map_err(|_| SrError::Ly)?;
+
+        self.nacm_check_operation(&node)
+    }
+}
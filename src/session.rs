@@ -1,23 +1,31 @@
-use crate::common::dup_str;
+use crate::common::{schema_path, timespec_to_system_time};
+use crate::connection::{ContextGuard, LogStore, RawConnection};
+use crate::data::{SrData, SrDataRef};
 use crate::enums::{
-    DefaultOperation, SrDatastore, SrEditFlag, SrGetOptions, SrNotifType,
+    DefaultOperation, SrDatastore, SrEditFlag, SrGetOptions, SrMovePosition,
+    SrNotifType,
 };
-use crate::errors::SrError;
+use crate::errors::{SrError, SrErrorInfo};
 use crate::str_to_cstring;
-use crate::subscription::{SrSubscription, SrSubscriptionId};
-use crate::value::SrValue;
+use crate::subscription::{
+    SrNotifEvent, SrNotifStreamOptions, SrSubscription, SrSubscriptionId,
+};
+use crate::value::{self, SrValue, ValType};
 use crate::values::SrValues;
-use libc::c_int;
+use libc::{c_int, c_void};
 use libyang3_sys::lyd_node;
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::mem::{zeroed, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
 use std::os::raw::c_char;
-use std::time::Duration;
+use std::sync::mpsc::Receiver;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use std::{fmt, ptr};
 use sysrepo_sys as ffi_sys;
-use yang3::context::Context;
-use yang3::data::{Data, DataTree};
+use yang3::data::{Data, DataFormat, DataNodeRef, DataPrinterFlags, DataTree};
+use yang3::schema::{DataValue, DataValueType};
 use yang3::utils::Binding;
 
 /// Event.
@@ -63,33 +71,72 @@ impl fmt::Display for SrEvent {
 
 pub type SrSessionId = *const ffi_sys::sr_session_ctx_t;
 
-pub struct SrSession {
-    raw_session: *mut ffi_sys::sr_session_ctx_t,
+/// Sysrepo session context, stopped when the last handle is dropped.
+pub(crate) struct RawSession {
+    raw: *mut ffi_sys::sr_session_ctx_t,
     owned: bool,
 
-    /// Map from raw pointer to subscription.
-    subscriptions: HashMap<SrSubscriptionId, SrSubscription>,
+    /// Keeps the connection open while the session is used.
+    _connection: Option<Arc<RawConnection>>,
 }
 
-impl Clone for SrSession {
-    fn clone(&self) -> Self {
-        Self {
-            raw_session: self.raw_session,
-            owned: false,
-            subscriptions: HashMap::new(),
+impl Drop for RawSession {
+    fn drop(&mut self) {
+        if self.owned {
+            unsafe {
+                ffi_sys::sr_session_stop(self.raw);
+            }
         }
     }
 }
 
+/// Sysrepo session, stopped when it and the subscriptions created with it
+/// are dropped. It can be moved to another thread, share it behind a mutex
+/// to use it from several threads.
+pub struct SrSession {
+    /// Map from raw pointer to subscription, dropped before the session.
+    pub(crate) subscriptions: HashMap<SrSubscriptionId, SrSubscription>,
+
+    raw_session: *mut ffi_sys::sr_session_ctx_t,
+    _handle: Arc<RawSession>,
+}
+
+unsafe impl Send for SrSession {}
+
 impl SrSession {
     pub fn from(sess: *mut ffi_sys::sr_session_ctx_t, owned: bool) -> Self {
+        Self::new(sess, owned, None)
+    }
+
+    /// Create an owned session that keeps its connection open.
+    pub(crate) fn from_connection(
+        sess: *mut ffi_sys::sr_session_ctx_t,
+        connection: Arc<RawConnection>,
+    ) -> Self {
+        Self::new(sess, true, Some(connection))
+    }
+
+    fn new(
+        sess: *mut ffi_sys::sr_session_ctx_t,
+        owned: bool,
+        connection: Option<Arc<RawConnection>>,
+    ) -> Self {
         Self {
-            raw_session: sess,
-            owned,
             subscriptions: HashMap::new(),
+            raw_session: sess,
+            _handle: Arc::new(RawSession {
+                raw: sess,
+                owned,
+                _connection: connection,
+            }),
         }
     }
 
+    /// Handle keeping the session and its connection alive.
+    pub(crate) fn handle(&self) -> Arc<RawSession> {
+        self._handle.clone()
+    }
+
     /// Get raw session context.
     pub unsafe fn get_raw_mut(&self) -> *mut ffi_sys::sr_session_ctx_t {
         self.raw_session
@@ -99,15 +146,15 @@ impl SrSession {
         self.raw_session
     }
 
-    /// Returns the libyang3 context associated with this Session
-    pub fn get_context(&self) -> ManuallyDrop<Context> {
-        let ctx = unsafe {
-            let ctx = ffi_sys::sr_acquire_context(
+    /// Acquire the libyang3 context of the session connection, released
+    /// when the guard is dropped.
+    pub fn get_context(&self) -> ContextGuard {
+        unsafe {
+            ContextGuard::acquire(
                 ffi_sys::sr_session_get_connection(self.raw_session),
-            ) as *mut libyang3_sys::ly_ctx;
-            Context::from_raw(&(), ctx)
-        };
-        ManuallyDrop::new(ctx)
+                self._handle._connection.clone(),
+            )
+        }
     }
 
     pub fn get_id(&self) -> u32 {
@@ -117,7 +164,7 @@ impl SrSession {
     pub fn switch_datastore(
         &self,
         datastore: SrDatastore,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let rc = unsafe {
             ffi_sys::sr_session_switch_ds(
                 self.raw_session,
@@ -125,7 +172,7 @@ impl SrSession {
             )
         };
         if rc != SrError::Ok as i32 {
-            return Err(SrError::from(rc));
+            return Err(self.error(rc));
         }
 
         Ok(())
@@ -136,8 +183,179 @@ impl SrSession {
         ds.into()
     }
 
+    /// Set the originator name, passed to the subscribers of the changes and
+    /// operations of this session.
+    pub fn set_orig_name(&mut self, name: &str) -> Result<(), SrErrorInfo> {
+        let name = str_to_cstring(name)?;
+        let rc = unsafe {
+            ffi_sys::sr_session_set_orig_name(self.raw_session, name.as_ptr())
+        };
+        if rc != SrError::Ok as i32 {
+            return Err(self.error(rc));
+        }
+
+        Ok(())
+    }
+
+    /// Get the originator name, in a callback the one of the event
+    /// originator.
+    pub fn get_orig_name(&self) -> Option<String> {
+        let name =
+            unsafe { ffi_sys::sr_session_get_orig_name(self.raw_session) };
+        if name.is_null() {
+            return None;
+        }
+
+        unsafe { Some(CStr::from_ptr(name).to_string_lossy().into_owned()) }
+    }
+
+    /// Push an originator data chunk, read back by index with
+    /// [`SrSession::get_orig_data`].
+    pub fn push_orig_data(&mut self, data: &[u8]) -> Result<(), SrErrorInfo> {
+        let rc = unsafe {
+            ffi_sys::sr_session_push_orig_data(
+                self.raw_session,
+                data.len() as u32,
+                data.as_ptr() as *const c_void,
+            )
+        };
+        if rc != SrError::Ok as i32 {
+            return Err(self.error(rc));
+        }
+
+        Ok(())
+    }
+
+    /// Remove all the pushed originator data.
+    pub fn del_orig_data(&mut self) {
+        unsafe { ffi_sys::sr_session_del_orig_data(self.raw_session) }
+    }
+
+    /// Get the originator data chunk at the index.
+    pub fn get_orig_data(&self, idx: u32) -> Result<Vec<u8>, SrErrorInfo> {
+        let mut size = 0;
+        let mut data = ptr::null();
+        let rc = unsafe {
+            ffi_sys::sr_session_get_orig_data(
+                self.raw_session,
+                idx,
+                &mut size,
+                &mut data,
+            )
+        };
+        if rc != SrError::Ok as i32 {
+            return Err(self.error(rc));
+        }
+
+        let data = unsafe {
+            std::slice::from_raw_parts(data as *const u8, size as usize)
+        };
+        Ok(data.to_vec())
+    }
+
+    /// Get the NETCONF session ID of the event originator, 0 if unknown.
+    pub fn get_event_nc_id(&self) -> u32 {
+        unsafe { ffi_sys::sr_session_get_event_nc_id(self.raw_session) }
+    }
+
+    /// Get the system user of the event originator.
+    pub fn get_event_user(&self) -> Option<String> {
+        let user =
+            unsafe { ffi_sys::sr_session_get_event_user(self.raw_session) };
+        if user.is_null() {
+            return None;
+        }
+
+        unsafe { Some(CStr::from_ptr(user).to_string_lossy().into_owned()) }
+    }
+
+    /// Get the sysrepo session ID of the event originator.
+    pub fn get_event_sid(&self) -> u32 {
+        unsafe { ffi_sys::sr_session_get_event_sid(self.raw_session) }
+    }
+
+    /// Lock the current datastore, or only a module of it. The lock is
+    /// released when the returned guard is dropped.
+    pub fn lock(
+        &mut self,
+        module_name: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<SrLockGuard<'_>, SrErrorInfo> {
+        let module_name = match module_name {
+            None => None,
+            Some(module_name) => Some(str_to_cstring(module_name)?),
+        };
+        let module_name_ptr =
+            module_name.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+        let timeout_ms =
+            timeout.map_or(0, |timeout| timeout.as_millis() as u32);
+
+        let rc = unsafe {
+            ffi_sys::sr_lock(self.raw_session, module_name_ptr, timeout_ms)
+        };
+        if rc != SrError::Ok as i32 {
+            return Err(self.error(rc));
+        }
+
+        Ok(SrLockGuard {
+            datastore: self.active_datastore(),
+            session: self,
+            module_name,
+        })
+    }
+
+    /// Get the lock state of a datastore, or only a module of it. The holder
+    /// is reported by its sysrepo session ID, sysrepo does not know its
+    /// NETCONF session ID.
+    pub fn lock_info(
+        &self,
+        datastore: SrDatastore,
+        module_name: Option<&str>,
+    ) -> Result<SrLockInfo, SrErrorInfo> {
+        let module_name = match module_name {
+            None => None,
+            Some(module_name) => Some(str_to_cstring(module_name)?),
+        };
+        let module_name_ptr =
+            module_name.as_ref().map_or(ptr::null(), |x| x.as_ptr());
+
+        let mut is_locked: c_int = 0;
+        let mut sid: u32 = 0;
+        let mut timestamp = unsafe { zeroed::<ffi_sys::timespec>() };
+
+        let rc = unsafe {
+            ffi_sys::sr_get_lock(
+                ffi_sys::sr_session_get_connection(self.raw_session),
+                datastore as ffi_sys::sr_datastore_t,
+                module_name_ptr,
+                &mut is_locked,
+                &mut sid,
+                &mut timestamp,
+            )
+        };
+        if rc != SrError::Ok as i32 {
+            return Err(self.error(rc));
+        }
+
+        if is_locked == 0 {
+            return Ok(SrLockInfo::default());
+        }
+
+        Ok(SrLockInfo {
+            is_locked: true,
+            sid,
+            timestamp: Some(timespec_to_system_time(&timestamp)),
+        })
+    }
+
+    /// Collect the error details of the last failed call.
+    pub(crate) fn error(&self, rc: c_int) -> SrErrorInfo {
+        let code = SrError::from(rc);
+        unsafe { SrErrorInfo::from_session(self.raw_session, code) }
+    }
+
     /// Insert subscription.
-    fn insert_subscription(
+    pub(crate) fn insert_subscription(
         &mut self,
         subscription: SrSubscription,
     ) -> SrSubscriptionId {
@@ -146,21 +364,24 @@ impl SrSession {
         id
     }
 
-    /// Remove subscription.
-    fn _remove_subscription(&mut self, subscription: &SrSubscription) {
-        let id = subscription.id();
-        self.subscriptions.remove(&id);
+    /// Remove subscription, dropping the returned subscription unsubscribes
+    /// it.
+    pub fn remove_subscription(
+        &mut self,
+        id: &SrSubscriptionId,
+    ) -> Option<SrSubscription> {
+        self.subscriptions.remove(id)
     }
 
     /// Get tree from given XPath.
     pub fn get_data<'a>(
         &mut self,
-        context: &'a Context,
+        context: &'a ContextGuard,
         xpath: &str,
         max_depth: u32,
         timeout: Option<Duration>,
         opts: SrGetOptions,
-    ) -> Result<DataTree<'a>, SrError> {
+    ) -> Result<SrData<'a>, SrErrorInfo> {
         let xpath = str_to_cstring(xpath)?;
         let timeout_ms =
             timeout.map_or(0, |timeout| timeout.as_millis() as u32);
@@ -180,23 +401,23 @@ impl SrSession {
         };
 
         if rc != SrError::Ok as i32 {
-            return Err(SrError::from(rc));
+            return Err(self.error(rc));
         }
 
         if data.is_null() {
-            return Err(SrError::NotFound);
+            return Err(SrError::NotFound.into());
         }
 
-        Ok(unsafe { DataTree::from_raw(context, (*data).tree) })
+        Ok(unsafe { SrData::from_raw(context, data) })
     }
 
     /// Get node by xpath
     pub fn get_node<'a>(
         &mut self,
-        context: &'a Context,
+        context: &'a ContextGuard,
         xpath: &str,
         timeout: Option<Duration>,
-    ) -> Result<DataTree<'a>, SrError> {
+    ) -> Result<SrData<'a>, SrErrorInfo> {
         let xpath = str_to_cstring(xpath)?;
         let timeout_ms =
             timeout.map_or(0, |timeout| timeout.as_millis() as u32);
@@ -214,11 +435,11 @@ impl SrSession {
         };
 
         if rc != SrError::Ok as i32 {
-            return Err(SrError::from(rc));
+            return Err(self.error(rc));
         }
 
         if data.is_null() {
-            return Err(SrError::NotFound);
+            return Err(SrError::NotFound.into());
         }
 
         let conn =
@@ -231,24 +452,24 @@ impl SrSession {
                 ffi_sys::sr_release_data(data);
             }
 
-            return Err(SrError::Internal);
+            return Err(SrError::Internal.into());
         }
 
-        Ok(unsafe { DataTree::from_raw(context, (*data).tree) })
+        Ok(unsafe { SrData::from_raw(context, data) })
     }
 
     pub fn edit_batch(
         &mut self,
         node: &DataTree<'_>,
         oper: DefaultOperation,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let oper = str_to_cstring(oper.as_str())?;
         let ret = unsafe {
             ffi_sys::sr_edit_batch(self.raw_session, node.raw(), oper.as_ptr())
         };
 
         if ret != SrError::Ok as i32 {
-            return Err(SrError::from(ret));
+            return Err(self.error(ret));
         }
 
         Ok(())
@@ -260,7 +481,7 @@ impl SrSession {
         xpath: &str,
         timeout: Option<Duration>,
         opts: u32,
-    ) -> Result<SrValues, SrError> {
+    ) -> Result<SrValues, SrErrorInfo> {
         let xpath = str_to_cstring(xpath)?;
         let timeout_ms =
             timeout.map_or(0, |timeout| timeout.as_millis() as u32);
@@ -280,7 +501,7 @@ impl SrSession {
         };
 
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(SrValues::from_raw(values, values_count, true))
         }
@@ -293,7 +514,7 @@ impl SrSession {
         value: Option<&str>,
         origin: Option<&str>,
         opts: u32,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let path = str_to_cstring(path)?;
 
         let value = match value {
@@ -321,7 +542,125 @@ impl SrSession {
             )
         };
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Set typed item to given Xpath. The data has to match the type of the
+    /// schema leaf, otherwise an `SrError::InvalArg` error is returned.
+    pub fn set_item(
+        &mut self,
+        path: &str,
+        data: value::Data,
+        origin: Option<&str>,
+        opts: u32,
+    ) -> Result<(), SrErrorInfo> {
+        let ctx = self.get_context();
+        let snode = ctx.find_path(&schema_path(path)).map_err(|_| {
+            SrErrorInfo::new(
+                SrError::NotFound,
+                format!("Schema node \"{path}\" not found."),
+            )
+        })?;
+        let leaf_type = snode.leaf_type().ok_or_else(|| {
+            SrErrorInfo::new(
+                SrError::InvalArg,
+                format!("Node \"{path}\" is not a leaf or leaf-list."),
+            )
+        })?;
+        if !SrValue::matches_type(&data, leaf_type.base_type()) {
+            return Err(SrErrorInfo::new(
+                SrError::InvalArg,
+                format!(
+                    "Value type {:?} does not match the type of \"{path}\".",
+                    ValType::from(&data)
+                ),
+            ));
+        }
+
+        let origin = match origin {
+            Some(orig) => Some(str_to_cstring(orig)?),
+            None => None,
+        };
+        let origin_ptr = origin
+            .as_ref()
+            .map_or(std::ptr::null(), |orig| orig.as_ptr());
+
+        let path = str_to_cstring(path)?;
+        let mut raw_value = unsafe { zeroed::<ffi_sys::sr_val_t>() };
+        let value = unsafe {
+            SrValue::new(&mut raw_value, String::new(), data, false, false)?
+        };
+
+        let rc = unsafe {
+            let rc = ffi_sys::sr_set_item(
+                self.raw_session,
+                path.as_ptr(),
+                value.as_raw(),
+                origin_ptr,
+                opts,
+            );
+            ffi_sys::sr_free_val_content(value.as_raw());
+            rc
+        };
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Move user-ordered list or leaf-list instance. `list_keys` (e.g.
+    /// `[name='a']`) or `leaflist_value` select the relative sibling for
+    /// `Before` and `After`.
+    pub fn move_item(
+        &mut self,
+        path: &str,
+        position: SrMovePosition,
+        list_keys: Option<&str>,
+        leaflist_value: Option<&str>,
+        origin: Option<&str>,
+        opts: u32,
+    ) -> Result<(), SrErrorInfo> {
+        let path = str_to_cstring(path)?;
+
+        let list_keys = match list_keys {
+            Some(keys) => Some(str_to_cstring(keys)?),
+            None => None,
+        };
+        let list_keys_ptr =
+            list_keys.as_ref().map_or(ptr::null(), |keys| keys.as_ptr());
+
+        let leaflist_value = match leaflist_value {
+            Some(value) => Some(str_to_cstring(value)?),
+            None => None,
+        };
+        let leaflist_value_ptr = leaflist_value
+            .as_ref()
+            .map_or(ptr::null(), |value| value.as_ptr());
+
+        let origin = match origin {
+            Some(orig) => Some(str_to_cstring(orig)?),
+            None => None,
+        };
+        let origin_ptr =
+            origin.as_ref().map_or(ptr::null(), |orig| orig.as_ptr());
+
+        let rc = unsafe {
+            ffi_sys::sr_move_item(
+                self.raw_session,
+                path.as_ptr(),
+                position as ffi_sys::sr_move_position_t,
+                list_keys_ptr,
+                leaflist_value_ptr,
+                origin_ptr,
+                opts,
+            )
+        };
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
         } else {
             Ok(())
         }
@@ -331,7 +670,7 @@ impl SrSession {
         &mut self,
         path: &str,
         option: SrEditFlag,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let path = CString::new(path).map_err(|_| SrError::Internal)?;
         let ret = unsafe {
             ffi_sys::sr_delete_item(
@@ -342,18 +681,20 @@ impl SrSession {
         };
 
         if ret != SrError::Ok as i32 {
-            Err(SrError::Internal)
+            Err(self.error(ret))
         } else {
             Ok(())
         }
     }
 
+    /// Replace the configuration of the module, or of all the modules, with
+    /// the tree, which sysrepo takes over.
     pub fn replace_config(
         &mut self,
-        node: Option<&DataTree<'_>>,
+        node: Option<DataTree<'_>>,
         module: Option<&str>,
         timeout: Option<Duration>,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let module = match module {
             None => None,
             Some(module) => Some(str_to_cstring(module)?),
@@ -362,7 +703,7 @@ impl SrSession {
         let timeout_ms =
             timeout.map_or(0, |timeout| timeout.as_millis() as u32);
 
-        let node_ptr = node.as_ref().map_or(ptr::null(), |x| x.raw()) as _;
+        let node_ptr = node.map_or(ptr::null_mut(), |x| x.into_raw()) as _;
 
         let ret = unsafe {
             ffi_sys::sr_replace_config(
@@ -374,7 +715,7 @@ impl SrSession {
         };
 
         if ret != SrError::Ok as i32 {
-            return Err(SrError::from(ret));
+            return Err(self.error(ret));
         }
 
         Ok(())
@@ -385,7 +726,7 @@ impl SrSession {
         source: SrDatastore,
         module_name: Option<&str>,
         timeout: Duration,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let module_name = match module_name {
             None => None,
             Some(module_name) => Some(str_to_cstring(module_name)?),
@@ -406,57 +747,195 @@ impl SrSession {
         };
 
         if ret != SrError::Ok as i32 {
-            return Err(SrError::from(ret));
+            return Err(self.error(ret));
         }
 
         Ok(())
     }
 
+    /// Validate the pending changes of the session, or the datastore content
+    /// if there are none. With a module, only the data of the module and
+    /// the modules it depends on are validated. All the validation errors
+    /// are returned.
+    pub fn validate(
+        &self,
+        module_name: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<(), SrErrorInfo> {
+        let module_name = match module_name {
+            Some(module_name) => Some(str_to_cstring(module_name)?),
+            None => None,
+        };
+        let timeout_ms =
+            timeout.map_or(0, |timeout| timeout.as_millis() as u32);
+
+        let ctx = self.get_context();
+        unsafe {
+            let _log = LogStore::new(ctx.raw());
+            let rc = ffi_sys::sr_validate(
+                self.raw_session,
+                module_name.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                timeout_ms,
+            );
+            if rc == SrError::Ok as i32 {
+                return Ok(());
+            }
+
+            // Libyang keeps every validation error with its path.
+            let err = SrErrorInfo::from_context(ctx.raw(), SrError::from(rc));
+            match err.errors().is_empty() {
+                true => Err(self.error(rc)),
+                false => Err(err),
+            }
+        }
+    }
+
     /// Apply changes for the session.
     pub fn apply_changes(
         &mut self,
         timeout: Option<Duration>,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let timeout_ms =
             timeout.map_or(0, |timeout| timeout.as_millis() as u32);
 
         let rc =
             unsafe { ffi_sys::sr_apply_changes(self.raw_session, timeout_ms) };
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(())
         }
     }
 
     /// Discard Changes
-    pub fn discard_changes(&self) -> Result<(), SrError> {
+    pub fn discard_changes(&self) -> Result<(), SrErrorInfo> {
         let rc = unsafe { ffi_sys::sr_discard_changes(self.raw_session) };
 
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(())
         }
     }
 
     // Discard Changes
-    pub fn discard_items(&self, path: &str) -> Result<(), SrError> {
+    pub fn discard_items(&self, path: &str) -> Result<(), SrErrorInfo> {
         let path = CString::new(path).map_err(|_| SrError::NotFound)?;
         let rc = unsafe {
             ffi_sys::sr_discard_changes_xpath(self.raw_session, path.as_ptr())
         };
 
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Delete an item from the operational data pushed by this session.
+    pub fn oper_delete_item(
+        &mut self,
+        path: &str,
+        value: Option<&str>,
+        origin: Option<&str>,
+        opts: u32,
+    ) -> Result<(), SrErrorInfo> {
+        let path = str_to_cstring(path)?;
+        let value = match value {
+            Some(value) => Some(str_to_cstring(value)?),
+            None => None,
+        };
+        let origin = match origin {
+            Some(origin) => Some(str_to_cstring(origin)?),
+            None => None,
+        };
+
+        let rc = unsafe {
+            ffi_sys::sr_oper_delete_item_str(
+                self.raw_session,
+                path.as_ptr(),
+                value.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                origin.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                opts,
+            )
+        };
+
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Discard the operational data pushed by this session, only the
+    /// subtrees selected by `xpath` if set.
+    pub fn discard_oper_changes(
+        &mut self,
+        xpath: Option<&str>,
+        timeout: Option<Duration>,
+    ) -> Result<(), SrErrorInfo> {
+        let xpath = match xpath {
+            Some(xpath) => Some(str_to_cstring(xpath)?),
+            None => None,
+        };
+        let timeout_ms =
+            timeout.map_or(0, |timeout| timeout.as_millis() as u32);
+
+        let rc = unsafe {
+            ffi_sys::sr_discard_oper_changes(
+                ffi_sys::sr_session_get_connection(self.raw_session),
+                self.raw_session,
+                xpath.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                timeout_ms,
+            )
+        };
+
+        if rc != SrError::Ok as i32 {
+            Err(self.error(rc))
         } else {
             Ok(())
         }
     }
 
+    /// Get the operational data pushed by this session, only for one
+    /// module if set.
+    pub fn get_oper_changes<'a>(
+        &mut self,
+        context: &'a ContextGuard,
+        module_name: Option<&str>,
+    ) -> Result<SrData<'a>, SrErrorInfo> {
+        let module_name = match module_name {
+            Some(name) => Some(str_to_cstring(name)?),
+            None => None,
+        };
+        let mut data: *mut ffi_sys::sr_data_t = std::ptr::null_mut();
+
+        let rc = unsafe {
+            ffi_sys::sr_get_oper_changes(
+                self.raw_session,
+                module_name.as_ref().map_or(ptr::null(), |x| x.as_ptr()),
+                &mut data,
+            )
+        };
+
+        if rc != SrError::Ok as i32 {
+            return Err(self.error(rc));
+        }
+
+        if data.is_null() {
+            return Err(SrError::NotFound.into());
+        }
+        let data = unsafe { SrData::from_raw(context, data) };
+        if data.raw().is_null() {
+            return Err(SrError::NotFound.into());
+        }
+
+        Ok(data)
+    }
+
     pub fn get_pending_changes<'a>(
         &self,
-        ctx: &'a Context,
+        ctx: &'a ContextGuard,
     ) -> Option<ManuallyDrop<DataTree<'a>>> {
         let node = unsafe { ffi_sys::sr_get_changes(self.raw_session) };
         if node.is_null() {
@@ -468,25 +947,60 @@ impl SrSession {
         }
     }
 
+    /// Get the changes of a module change event as a diff tree, with the
+    /// `yang:operation` metadata on the changed nodes.
+    pub fn get_change_diff<'a>(
+        &'a self,
+        ctx: &'a ContextGuard,
+    ) -> Result<SrDataRef<'a>, SrErrorInfo> {
+        let mut diff = ptr::null();
+        let rc =
+            unsafe { ffi_sys::sr_get_change_diff(self.raw_session, &mut diff) };
+        if rc != SrError::Ok as i32 {
+            return Err(self.error(rc));
+        }
+        if diff.is_null() {
+            return Err(SrError::NotFound.into());
+        }
+
+        Ok(unsafe { SrDataRef::from_raw(ctx, diff as *mut _) })
+    }
+
+    /// Print the change diff of a module change event in the format, which
+    /// may be the binary LYB.
+    pub fn print_change_diff(
+        &self,
+        format: DataFormat,
+    ) -> Result<Vec<u8>, SrErrorInfo> {
+        let ctx = self.get_context();
+        let diff = self.get_change_diff(&ctx)?;
+        let data = diff
+            .print_bytes(format, DataPrinterFlags::WITH_SIBLINGS)
+            .map_err(|_| SrError::Ly)?;
+
+        Ok(data)
+    }
+
     /// Subscribe event notification.
     pub fn on_notif_subscribe<F>(
         &mut self,
         module_name: &str,
         xpath: Option<&str>,
-        start_time: Option<*mut ffi_sys::timespec>,
-        stop_time: Option<*mut ffi_sys::timespec>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
         callback: F,
         opts: ffi_sys::sr_subscr_options_t,
-    ) -> Result<&mut SrSubscription, SrError>
+    ) -> Result<&mut SrSubscription, SrErrorInfo>
     where
         F: FnMut(
-                SrSession,
+                &mut SrSession,
                 u32,
                 SrNotifType,
                 Option<&str>,
                 SrValues,
-                *mut ffi_sys::timespec,
-            ) + 'static,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
     {
         let sub = SrSubscription::on_notification_subscribe(
             self,
@@ -496,7 +1010,8 @@ impl SrSession {
             stop_time,
             callback,
             opts,
-        )?;
+        )
+        .map_err(|err| self.error(err as c_int))?;
         let id = self.insert_subscription(sub);
         Ok(self.subscriptions.get_mut(&id).unwrap())
     }
@@ -505,19 +1020,20 @@ impl SrSession {
         &mut self,
         module_name: &str,
         xpath: Option<&str>,
-        start_time: Option<*mut ffi_sys::timespec>,
-        stop_time: Option<*mut ffi_sys::timespec>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
         callback: F,
         opts: ffi_sys::sr_subscr_options_t,
-    ) -> Result<&mut SrSubscription, SrError>
+    ) -> Result<&mut SrSubscription, SrErrorInfo>
     where
         F: FnMut(
-            &SrSession,
-            u32,
-            SrNotifType,
-            &DataTree<'_>,
-            *mut ffi_sys::timespec,
-        ),
+                &SrSession,
+                u32,
+                SrNotifType,
+                &DataTree<'_>,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
     {
         let sub = SrSubscription::on_notification_subscribe_tree(
             self,
@@ -527,11 +1043,38 @@ impl SrSession {
             stop_time,
             callback,
             opts,
-        )?;
+        )
+        .map_err(|err| self.error(err as c_int))?;
         let id = self.insert_subscription(sub);
         Ok(self.subscriptions.get_mut(&id).unwrap())
     }
 
+    /// Subscribe event notification, the events are sent to the returned
+    /// receiver as owned `SrNotifEvent`.
+    pub fn on_notif_subscribe_stream(
+        &mut self,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        options: SrNotifStreamOptions,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<(&mut SrSubscription, Receiver<SrNotifEvent>), SrErrorInfo>
+    {
+        let (sub, receiver) = SrSubscription::on_notification_stream(
+            self,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            options,
+            opts,
+        )
+        .map_err(|err| self.error(err as c_int))?;
+        let id = self.insert_subscription(sub);
+        Ok((self.subscriptions.get_mut(&id).unwrap(), receiver))
+    }
+
     /// Subscribe RPC.
     pub fn on_rpc_subscribe<F>(
         &mut self,
@@ -539,14 +1082,23 @@ impl SrSession {
         callback: F,
         priority: u32,
         opts: ffi_sys::sr_subscr_options_t,
-    ) -> Result<&mut SrSubscription, SrError>
+    ) -> Result<&mut SrSubscription, SrErrorInfo>
     where
-        F: FnMut(SrSession, u32, &str, SrValues, SrEvent, u32) -> SrValues
+        F: FnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                SrValues,
+                SrEvent,
+                u32,
+            ) -> Result<SrValues, SrErrorInfo>
+            + Send
             + 'static,
     {
         let sub = SrSubscription::on_rpc_subscribe(
             self, xpath, callback, priority, opts,
-        )?;
+        )
+        .map_err(|err| self.error(err as c_int))?;
         let id = self.insert_subscription(sub);
         Ok(self.subscriptions.get_mut(&id).unwrap())
     }
@@ -557,44 +1109,53 @@ impl SrSession {
         callback: F,
         priority: u32,
         opts: ffi_sys::sr_subscr_options_t,
-    ) -> Result<&mut SrSubscription, SrError>
+    ) -> Result<&mut SrSubscription, SrErrorInfo>
     where
         F: for<'a> FnMut(
-            &'a mut SrSession,
-            &'a Context,
-            u32,
-            &str,
-            &DataTree<'a>,
-            &mut DataTree<'a>,
-            SrEvent,
-            u32,
-        ),
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &str,
+                &DataTree<'a>,
+                &mut DataTree<'a>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
     {
         let sub = SrSubscription::on_rpc_subscribe_tree(
             self, xpath, callback, priority, opts,
-        )?;
+        )
+        .map_err(|err| self.error(err as c_int))?;
         let id = self.insert_subscription(sub);
         Ok(self.subscriptions.get_mut(&id).unwrap())
     }
 
+    /// Subscribe operational data provider. The callback borrows the parent
+    /// of the requested nodes from sysrepo, if there is one, and either adds
+    /// the data to it and returns `None`, or returns a new tree.
     pub fn on_oper_get_subscribe<F>(
         &mut self,
         module_name: &str,
         xpath: &str,
         callback: F,
         opts: ffi_sys::sr_subscr_options_t,
-    ) -> Result<&mut SrSubscription, SrError>
+    ) -> Result<&mut SrSubscription, SrErrorInfo>
     where
         F: for<'a> FnMut(
-            &'a mut SrSession,
-            &'a Context,
-            u32,
-            &'a str,
-            &'a str,
-            Option<&'a str>,
-            u32,
-            Option<DataTree<'a>>,
-        ) -> Result<Option<DataTree<'a>>, SrError>,
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &'a str,
+                &'a str,
+                Option<&'a str>,
+                u32,
+                Option<&'a mut DataTree<'a>>,
+            )
+                -> Result<Option<DataTree<'a>>, SrErrorInfo>
+            + Send
+            + 'static,
     {
         let sub = SrSubscription::on_oper_get_subscribe(
             self,
@@ -602,7 +1163,31 @@ impl SrSession {
             xpath,
             callback,
             opts,
-        )?;
+        )
+        .map_err(|err| self.error(err as c_int))?;
+        let id = self.insert_subscription(sub);
+        Ok(self.subscriptions.get_mut(&id).unwrap())
+    }
+
+    /// Subscribe operational poll, caching the data of the oper get
+    /// subscription on `xpath` for `valid_ms`. With
+    /// `SrSubcribeFlag::OperPollDiff` the changes are reported to module
+    /// change subscribers of the operational datastore.
+    pub fn on_oper_poll_subscribe(
+        &mut self,
+        module_name: &str,
+        xpath: &str,
+        valid_ms: u32,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<&mut SrSubscription, SrErrorInfo> {
+        let sub = SrSubscription::on_oper_poll_subscribe(
+            self,
+            module_name,
+            xpath,
+            valid_ms,
+            opts,
+        )
+        .map_err(|err| self.error(err as c_int))?;
         let id = self.insert_subscription(sub);
         Ok(self.subscriptions.get_mut(&id).unwrap())
     }
@@ -615,20 +1200,23 @@ impl SrSession {
         callback: F,
         priority: u32,
         opts: ffi_sys::sr_subscr_options_t,
-    ) -> Result<&mut SrSubscription, SrError>
+    ) -> Result<&mut SrSubscription, SrErrorInfo>
     where
         F: FnMut(
-            SrSession,
-            u32,
-            &str,
-            Option<&str>,
-            SrEvent,
-            u32,
-        ) -> Result<(), SrError>,
+                &mut SrSession,
+                u32,
+                &str,
+                Option<&str>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
     {
         let sub = SrSubscription::on_module_change(
             self, mod_name, path, callback, priority, opts,
-        )?;
+        )
+        .map_err(|err| self.error(err as c_int))?;
         let id = self.insert_subscription(sub);
         Ok(self.subscriptions.get_mut(&id).unwrap())
     }
@@ -637,7 +1225,7 @@ impl SrSession {
     pub fn get_changes_iter(
         &self,
         path: &str,
-    ) -> Result<SrChangeIterator<'_>, SrError> {
+    ) -> Result<SrChangeIterator<'_>, SrErrorInfo> {
         let mut it = unsafe { zeroed::<*mut ffi_sys::sr_change_iter_t>() };
 
         let path = str_to_cstring(path)?;
@@ -650,16 +1238,17 @@ impl SrSession {
         };
 
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(SrChangeIterator::from(self, it))
         }
     }
 
+    /// Get changes iter yielding the changed data nodes.
     pub fn get_changes_iter_tree(
         &self,
         path: &str,
-    ) -> Result<SrChangeIteratorTree<'_>, SrError> {
+    ) -> Result<SrChangeIteratorTree<'_>, SrErrorInfo> {
         let mut it = unsafe { zeroed::<*mut ffi_sys::sr_change_iter_t>() };
         let path = str_to_cstring(path)?;
         let rc = unsafe {
@@ -670,7 +1259,7 @@ impl SrSession {
             )
         };
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(SrChangeIteratorTree::from(self, it))
         }
@@ -682,7 +1271,7 @@ impl SrSession {
         notif: &DataTree<'_>,
         timeout_ms: u32,
         wait: i32,
-    ) -> Result<(), SrError> {
+    ) -> Result<(), SrErrorInfo> {
         let rc = unsafe {
             ffi_sys::sr_notif_send_tree(
                 self.raw_session,
@@ -692,7 +1281,7 @@ impl SrSession {
             )
         };
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(())
         }
@@ -705,13 +1294,13 @@ impl SrSession {
         values: &SrValues,
         timeout_ms: u32,
         wait: i32,
-    ) -> Result<(), SrError> {
-        let xpath = dup_str(xpath)?;
+    ) -> Result<(), SrErrorInfo> {
+        let xpath = str_to_cstring(xpath)?;
         let (values, len) = values.as_raw();
         let rc = unsafe {
             ffi_sys::sr_notif_send(
                 self.raw_session,
-                xpath,
+                xpath.as_ptr(),
                 values,
                 len,
                 timeout_ms,
@@ -719,7 +1308,7 @@ impl SrSession {
             )
         };
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(())
         }
@@ -731,8 +1320,8 @@ impl SrSession {
         xpath: &str,
         input: Option<SrValues>,
         timeout: Option<Duration>,
-    ) -> Result<SrValues, SrError> {
-        let xpath = dup_str(xpath)?;
+    ) -> Result<SrValues, SrErrorInfo> {
+        let xpath = str_to_cstring(xpath)?;
 
         let (input, input_cnt) = match input {
             None => (std::ptr::null_mut(), 0),
@@ -748,7 +1337,7 @@ impl SrSession {
         let rc = unsafe {
             ffi_sys::sr_rpc_send(
                 self.raw_session,
-                xpath,
+                xpath.as_ptr(),
                 input,
                 input_cnt,
                 timeout,
@@ -758,23 +1347,20 @@ impl SrSession {
         };
 
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
         } else {
             Ok(SrValues::from_raw(output, output_count, false))
         }
     }
 
-    /// Send RPC Tree
+    /// Send RPC Tree, the input stays owned by the caller.
     pub fn rpc_send_tree<'a>(
         &mut self,
-        ctx: &'a Context,
-        input: Option<DataTree<'a>>,
+        ctx: &'a ContextGuard,
+        input: Option<&DataTree<'a>>,
         timeout: Option<Duration>,
-    ) -> Result<DataTree<'a>, SrError> {
-        let input = match input {
-            None => std::ptr::null_mut(),
-            Some(input) => input.into_raw(),
-        };
+    ) -> Result<SrData<'a>, SrErrorInfo> {
+        let input = input.map_or(std::ptr::null_mut(), |input| input.raw());
 
         let timeout = timeout.map_or(0, |timeout| timeout.as_millis() as u32);
 
@@ -790,14 +1376,11 @@ impl SrSession {
         };
 
         if rc != SrError::Ok as i32 {
-            Err(SrError::from(rc))
+            Err(self.error(rc))
+        } else if output.is_null() {
+            Err(SrError::NotFound.into())
         } else {
-            let output = unsafe {
-                let output = (*output).tree;
-                DataTree::from_raw(ctx, output)
-            };
-
-            Ok(output)
+            Ok(unsafe { SrData::from_raw(ctx, output) })
         }
     }
 
@@ -839,7 +1422,7 @@ impl SrSession {
         xpath: &str,
         node_name: &str,
         key_name: &str,
-    ) -> Result<String, SrError> {
+    ) -> Result<String, SrErrorInfo> {
         let xpath = CString::new(xpath).unwrap();
         let node_name = CString::new(node_name).unwrap();
         let key_name = CString::new(key_name).unwrap();
@@ -861,25 +1444,70 @@ impl SrSession {
             )
         };
         if ret.is_null() {
-            return Err(SrError::NotFound);
+            return Err(SrError::NotFound.into());
         }
 
         unsafe { Ok(CStr::from_ptr(ret).to_str().unwrap().to_string()) }
     }
 }
 
-impl Drop for SrSession {
-    fn drop(&mut self) {
-        if self.owned {
-            self.subscriptions.drain();
+/// Datastore lock, released on drop.
+pub struct SrLockGuard<'a> {
+    session: &'a mut SrSession,
+    /// Locked datastore, the session may have switched to another one.
+    datastore: SrDatastore,
+    module_name: Option<CString>,
+}
 
-            unsafe {
-                ffi_sys::sr_session_stop(self.raw_session);
-            }
+impl Deref for SrLockGuard<'_> {
+    type Target = SrSession;
+
+    fn deref(&self) -> &Self::Target {
+        self.session
+    }
+}
+
+impl DerefMut for SrLockGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.session
+    }
+}
+
+impl Drop for SrLockGuard<'_> {
+    fn drop(&mut self) {
+        let module_name_ptr = self
+            .module_name
+            .as_ref()
+            .map_or(ptr::null(), |x| x.as_ptr());
+        let active = self.session.active_datastore();
+        let switch = active != self.datastore;
+        if switch {
+            let _ = self.session.switch_datastore(self.datastore.clone());
+        }
+        unsafe {
+            ffi_sys::sr_unlock(self.session.raw_session, module_name_ptr);
+        }
+        if switch {
+            let _ = self.session.switch_datastore(active);
         }
     }
 }
 
+/// Lock holder information.
+///
+/// There is no NETCONF session ID of the holder: sysrepo only records the
+/// sysrepo session ID with a lock, and only event sessions carry a NETCONF
+/// session ID (see [`SrSession::get_event_nc_id`]). A NETCONF server maps
+/// `sid` to its own sessions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SrLockInfo {
+    pub is_locked: bool,
+    /// Sysrepo session ID of the lock owner.
+    pub sid: u32,
+    /// Time the lock was acquired.
+    pub timestamp: Option<SystemTime>,
+}
+
 /// Change Oper.
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub enum SrChangeOper {
@@ -1003,11 +1631,15 @@ impl Iterator for SrChangeIterator<'_> {
                         OperationData::without_prev_value(new_value),
                     ),
                     SrChangeOper::Moved => {
-                        let old_value =
-                            unsafe { SrValue::from(old_value, false) };
+                        // Previous sibling, none if moved to the first place.
+                        let old_value = match old_value.is_null() {
+                            true => None,
+                            false => unsafe {
+                                Some(SrValue::from(old_value, false))
+                            },
+                        };
                         SrChangeOperation::Moved(OperationData::new(
-                            new_value,
-                            Some(old_value),
+                            new_value, old_value,
                         ))
                     }
                 },
@@ -1028,11 +1660,13 @@ impl Drop for SrChangeIterator<'_> {
     }
 }
 
+/// Sysrepo Changes Iterator yielding the changed data nodes, iterate over a
+/// reference: the nodes are valid while the iterator is.
 pub struct SrChangeIteratorTree<'a> {
     /// Raw pointer to iter.
     iter: *mut ffi_sys::sr_change_iter_t,
     session: &'a SrSession,
-    _ctx: ManuallyDrop<Context>,
+    ctx: ContextGuard,
 }
 
 impl<'a> SrChangeIteratorTree<'a> {
@@ -1040,43 +1674,62 @@ impl<'a> SrChangeIteratorTree<'a> {
         session: &'a SrSession,
         iter: *mut ffi_sys::sr_change_iter_t,
     ) -> Self {
-        let _ctx = session.get_context();
-        Self {
-            session,
-            iter,
-            _ctx,
-        }
+        let ctx = session.get_context();
+        Self { session, iter, ctx }
     }
 
-    pub fn iter(&mut self) -> *mut ffi_sys::sr_change_iter_t {
+    pub fn iter(&self) -> *mut ffi_sys::sr_change_iter_t {
         self.iter
     }
 }
 
-pub struct OperationDataTree {
-    pub node: *const lyd_node,
-    pub prev_value: Option<String>,
+impl Drop for SrChangeIteratorTree<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            ffi_sys::sr_free_change_iter(self.iter);
+        }
+    }
+}
+
+pub struct OperationDataTree<'a> {
+    /// Changed node, not owned.
+    tree: ManuallyDrop<DataTree<'a>>,
+
+    /// Previous value of a modified leaf.
+    pub prev_value: Option<DataValue>,
+
+    /// Previous instance of a moved user-ordered list (keys predicate) or
+    /// leaf-list (value), `None` if moved to the first place.
     pub prev_list: Option<String>,
+
+    /// Whether the previous value of a modified leaf was the default one.
     pub prev_default_value: bool,
 }
 
-pub enum SrChangeOperationTree {
-    Created(OperationDataTree),
-    Modified(OperationDataTree),
-    Deleted(OperationDataTree),
-    Moved(OperationDataTree),
+impl OperationDataTree<'_> {
+    /// Changed node.
+    pub fn node(&self) -> DataNodeRef<'_> {
+        unsafe { DataNodeRef::from_raw(&self.tree, self.tree.raw()) }
+    }
+}
+
+pub enum SrChangeOperationTree<'a> {
+    Created(OperationDataTree<'a>),
+    Modified(OperationDataTree<'a>),
+    Deleted(OperationDataTree<'a>),
+    Moved(OperationDataTree<'a>),
 }
 
-impl SrChangeOperationTree {
+impl<'a> SrChangeOperationTree<'a> {
     pub fn from(
         op: SrChangeOper,
-        node: *const lyd_node,
-        prev_value: Option<String>,
+        node: DataTree<'a>,
+        prev_value: Option<DataValue>,
         prev_list: Option<String>,
         prev_dflt: bool,
     ) -> Self {
         let operation_data = OperationDataTree {
-            node,
+            tree: ManuallyDrop::new(node),
             prev_value,
             prev_list,
             prev_default_value: prev_dflt,
@@ -1094,12 +1747,42 @@ impl SrChangeOperationTree {
             SrChangeOper::Moved => SrChangeOperationTree::Moved(operation_data),
         }
     }
+
+    /// Change details.
+    pub fn data(&self) -> &OperationDataTree<'a> {
+        match self {
+            SrChangeOperationTree::Created(data)
+            | SrChangeOperationTree::Modified(data)
+            | SrChangeOperationTree::Deleted(data)
+            | SrChangeOperationTree::Moved(data) => data,
+        }
+    }
+}
+
+/// Parse a canonical value of a leaf of the given base type, values of the
+/// other types are kept as strings.
+fn parse_value(value: String, base_type: DataValueType) -> DataValue {
+    let parsed = match base_type {
+        DataValueType::Uint8 => value.parse().ok().map(DataValue::Uint8),
+        DataValueType::Uint16 => value.parse().ok().map(DataValue::Uint16),
+        DataValueType::Uint32 => value.parse().ok().map(DataValue::Uint32),
+        DataValueType::Uint64 => value.parse().ok().map(DataValue::Uint64),
+        DataValueType::Int8 => value.parse().ok().map(DataValue::Int8),
+        DataValueType::Int16 => value.parse().ok().map(DataValue::Int16),
+        DataValueType::Int32 => value.parse().ok().map(DataValue::Int32),
+        DataValueType::Int64 => value.parse().ok().map(DataValue::Int64),
+        DataValueType::Bool => value.parse().ok().map(DataValue::Bool),
+        DataValueType::Empty => Some(DataValue::Empty),
+        _ => None,
+    };
+    parsed.unwrap_or(DataValue::Other(value))
 }
 
-impl Iterator for SrChangeIteratorTree<'_> {
-    type Item = SrChangeOperationTree;
+impl<'a> Iterator for &'a SrChangeIteratorTree<'_> {
+    type Item = SrChangeOperationTree<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let iter: &'a SrChangeIteratorTree<'_> = *self;
         let mut oper: ffi_sys::sr_change_oper_t = 0;
         let mut node: *const lyd_node = std::ptr::null_mut();
         let mut prev_value: *const c_char = std::ptr::null_mut();
@@ -1107,8 +1790,8 @@ impl Iterator for SrChangeIteratorTree<'_> {
         let mut prev_default_value: c_int = 0;
         let rc = unsafe {
             ffi_sys::sr_get_change_tree_next(
-                self.session.get_raw_mut(),
-                self.iter(),
+                iter.session.get_raw_mut(),
+                iter.iter(),
                 &mut oper,
                 &mut node,
                 &mut prev_value,
@@ -1117,7 +1800,8 @@ impl Iterator for SrChangeIteratorTree<'_> {
             )
         };
 
-        if rc == SrError::NotFound as _ {
+        // Stop after the last change or on any error.
+        if rc != SrError::Ok as i32 || node.is_null() {
             return None;
         }
 
@@ -1126,6 +1810,7 @@ impl Iterator for SrChangeIteratorTree<'_> {
             Err(_) => return None,
         };
 
+        let node = unsafe { DataTree::from_raw(&iter.ctx, node as *mut _) };
         let prev_value = match prev_value.is_null() {
             true => None,
             false => unsafe {
@@ -1139,6 +1824,22 @@ impl Iterator for SrChangeIteratorTree<'_> {
             }),
         };
 
+        // A moved leaf-list reports its previous instance as the value.
+        let (prev_value, prev_list) = match oper {
+            SrChangeOper::Moved => (None, prev_list.or(prev_value)),
+            _ => {
+                let base_type = node
+                    .reference()
+                    .and_then(|node| node.schema().leaf_type())
+                    .map(|leaf_type| leaf_type.base_type());
+                let prev_value = prev_value.map(|value| match base_type {
+                    Some(base_type) => parse_value(value, base_type),
+                    None => DataValue::Other(value),
+                });
+                (prev_value, prev_list)
+            }
+        };
+
         let prev_default_value = prev_default_value > 0;
 
         Some(SrChangeOperationTree::from(
@@ -1,24 +1,113 @@
-use crate::common::{dup_str, str_to_cstring};
-use crate::enums::SrNotifType;
-use crate::errors::SrError;
-use crate::session::{SrEvent, SrSession};
+use crate::common::{
+    str_to_cstring, system_time_to_timespec, timespec_to_system_time,
+};
+use crate::connection::ContextGuard;
+use crate::enums::{SrLogLevel, SrNotifType};
+use crate::errors::{NetconfError, SrError, SrErrorInfo};
+use crate::session::{RawSession, SrEvent, SrSession};
 use crate::values::SrValues;
+use libc::c_int;
 use libyang3_sys::lyd_node;
+use std::any::Any;
+use std::collections::HashMap;
 use std::ffi::CStr;
-use std::mem::{zeroed, ManuallyDrop};
+use std::mem::ManuallyDrop;
+use std::os::fd::RawFd;
 use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
 use sysrepo_sys as ffi_sys;
 
-use yang3::context::Context;
-use yang3::data::DataTree;
+use yang3::data::{
+    Data, DataFormat, DataOperation, DataPrinterFlags, DataTree,
+};
 use yang3::utils::Binding;
 
 pub type SrSubscriptionId = *const ffi_sys::sr_subscription_ctx_t;
 
+/// Owned notification event, received from a notification stream.
+#[derive(Clone, Debug)]
+pub struct SrNotifEvent {
+    /// Notification type.
+    pub notif_type: SrNotifType,
+    /// Time the notification was generated, if sysrepo passed one.
+    pub timestamp: Option<SystemTime>,
+    /// Format of `data`.
+    pub format: DataFormat,
+    /// Serialized notification, `None` for events without a notification
+    /// such as `SrNotifType::ReplayComplete`.
+    pub data: Option<String>,
+}
+
+impl SrNotifEvent {
+    /// Parse the notification back into a tree.
+    pub fn tree<'a>(
+        &self,
+        ctx: &'a ContextGuard,
+    ) -> Option<yang3::Result<DataTree<'a>>> {
+        self.data.as_ref().map(|data| {
+            DataTree::parse_op_string(
+                ctx,
+                data,
+                self.format,
+                DataOperation::NotificationYang,
+            )
+        })
+    }
+}
+
+/// Buffering of a notification stream.
+#[derive(Clone, Debug)]
+pub struct SrNotifStreamOptions {
+    /// Number of events buffered before the receiver falls behind.
+    pub capacity: usize,
+    /// Block the sysrepo thread while the buffer is full. Otherwise the
+    /// events that do not fit are dropped.
+    pub block: bool,
+    /// Format the notifications are serialized to.
+    pub format: DataFormat,
+}
+
+impl Default for SrNotifStreamOptions {
+    fn default() -> Self {
+        Self {
+            capacity: 64,
+            block: true,
+            format: DataFormat::JSON,
+        }
+    }
+}
+
+/// Hook called with the message of a panicking subscription callback.
+static PANIC_HOOK: RwLock<Option<Box<dyn Fn(&str) + Send + Sync>>> =
+    RwLock::new(None);
+
+/// Set a hook to observe panics of subscription callbacks. The panic is
+/// caught and the event fails with `SrError::CallbackFailed`.
+pub fn set_callback_panic_hook<F>(hook: F)
+where
+    F: Fn(&str) + Send + Sync + 'static,
+{
+    let mut panic_hook = PANIC_HOOK.write().unwrap_or_else(|e| e.into_inner());
+    *panic_hook = Some(Box::new(hook));
+}
+
+/// Callback closure owned by a subscription.
+type SrCallback = Box<dyn Send>;
+
 /// Sysrepo Subscription.
 pub struct SrSubscription {
     /// Raw Pointer to subscription.
     raw_subscription: *mut ffi_sys::sr_subscription_ctx_t,
+
+    /// Map from sub-id to its callback, dropped after unsubscribe.
+    callbacks: HashMap<u32, SrCallback>,
+
+    /// Sessions the subscriptions were created with, kept alive until after
+    /// unsubscribe.
+    sessions: Vec<Arc<RawSession>>,
 }
 
 impl SrSubscription {
@@ -33,12 +122,257 @@ impl SrSubscription {
     pub fn from(subscr: *mut ffi_sys::sr_subscription_ctx_t) -> Self {
         Self {
             raw_subscription: subscr,
+            callbacks: HashMap::new(),
+            sessions: Vec::new(),
+        }
+    }
+
+    /// Keep the session, and with it the connection, open while this
+    /// subscription context is alive.
+    pub(crate) fn keep_session(&mut self, session: &SrSession) {
+        let handle = session.handle();
+        if !self.sessions.iter().any(|kept| Arc::ptr_eq(kept, &handle)) {
+            self.sessions.push(handle);
         }
     }
 
     pub fn id(&self) -> SrSubscriptionId {
         self.raw_subscription
     }
+
+    /// Get the event pipe, readable when a subscription created with
+    /// `SrSubcribeFlag::NoThread` has events to process.
+    pub fn event_pipe(&self) -> Result<RawFd, SrError> {
+        let mut fd: c_int = -1;
+        let rc = unsafe {
+            ffi_sys::sr_get_event_pipe(self.raw_subscription, &mut fd)
+        };
+        match rc {
+            0 => Ok(fd),
+            rc => Err(SrError::from(rc)),
+        }
+    }
+
+    /// Process pending events of a subscription created with
+    /// `SrSubcribeFlag::NoThread`, calling the callbacks on this thread.
+    pub fn process_events(&self) -> Result<(), SrError> {
+        let rc = unsafe {
+            ffi_sys::sr_subscription_process_events(
+                self.raw_subscription,
+                std::ptr::null_mut(),
+                std::ptr::null_mut(),
+            )
+        };
+        match rc {
+            0 => Ok(()),
+            rc => Err(SrError::from(rc)),
+        }
+    }
+
+    /// Get the sub-id of the last subscription added to this context.
+    pub fn last_sub_id(&self) -> u32 {
+        unsafe {
+            ffi_sys::sr_subscription_get_last_sub_id(self.raw_subscription)
+        }
+    }
+
+    /// Suspend a subscription, its callbacks are not called until resumed.
+    pub fn suspend(&self, sub_id: u32) -> Result<(), SrError> {
+        let rc = unsafe {
+            ffi_sys::sr_subscription_suspend(self.raw_subscription, sub_id)
+        };
+        match rc {
+            0 => Ok(()),
+            rc => Err(SrError::from(rc)),
+        }
+    }
+
+    /// Resume a suspended subscription.
+    pub fn resume(&self, sub_id: u32) -> Result<(), SrError> {
+        let rc = unsafe {
+            ffi_sys::sr_subscription_resume(self.raw_subscription, sub_id)
+        };
+        match rc {
+            0 => Ok(()),
+            rc => Err(SrError::from(rc)),
+        }
+    }
+
+    /// Check whether a subscription is suspended.
+    pub fn is_suspended(&self, sub_id: u32) -> Result<bool, SrError> {
+        let mut suspended: c_int = 0;
+        let rc = unsafe {
+            ffi_sys::sr_subscription_get_suspended(
+                self.raw_subscription,
+                sub_id,
+                &mut suspended,
+            )
+        };
+        match rc {
+            0 => Ok(suspended != 0),
+            rc => Err(SrError::from(rc)),
+        }
+    }
+
+    /// Remove one subscription from this context, the others are kept.
+    pub fn unsubscribe_sub(&mut self, sub_id: u32) -> Result<(), SrError> {
+        let rc = unsafe {
+            ffi_sys::sr_unsubscribe_sub(self.raw_subscription, sub_id)
+        };
+        match rc {
+            0 => {
+                self.callbacks.remove(&sub_id);
+                Ok(())
+            }
+            rc => Err(SrError::from(rc)),
+        }
+    }
+}
+
+unsafe impl Send for SrSubscription {}
+
+impl SrSubscription {
+    /// Run a callback trampoline. Panics and invalid arguments are logged
+    /// and fail the event with `SrError::CallbackFailed` instead of
+    /// unwinding into sysrepo.
+    unsafe fn guard<F>(sess: *mut ffi_sys::sr_session_ctx_t, f: F) -> i32
+    where
+        F: FnOnce() -> Result<i32, String>,
+    {
+        let message = match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(Ok(rc)) => return rc,
+            Ok(Err(message)) => message,
+            Err(payload) => {
+                let message = panic_message(payload.as_ref());
+                if let Ok(hook) = PANIC_HOOK.read() {
+                    if let Some(hook) = hook.as_ref() {
+                        hook(&message);
+                    }
+                }
+                format!("Callback panicked: {message}")
+            }
+        };
+
+        Self::log_failure(&message);
+        Self::set_callback_error(
+            sess,
+            &SrErrorInfo::new(SrError::CallbackFailed, message),
+        )
+    }
+
+    /// Log the failure of a callback through sysrepo, which does not report
+    /// failed notification callbacks at all.
+    fn log_failure(message: &str) {
+        let Ok(message) = str_to_cstring(message) else {
+            return;
+        };
+        unsafe {
+            ffi_sys::srplg_log(
+                c"sysrepo-rs".as_ptr(),
+                SrLogLevel::Error as ffi_sys::sr_log_level_t,
+                c"%s".as_ptr(),
+                message.as_ptr(),
+            );
+        }
+    }
+
+    /// Convert a string argument of a callback.
+    unsafe fn arg_str<'a>(s: *const c_char) -> Result<&'a str, String> {
+        let s = CStr::from_ptr(s);
+        s.to_str()
+            .map_err(|_| format!("Callback argument is not valid UTF-8: {s:?}"))
+    }
+
+    /// Convert an optional string argument of a callback.
+    unsafe fn arg_opt_str<'a>(
+        s: *const c_char,
+    ) -> Result<Option<&'a str>, String> {
+        if s.is_null() {
+            Ok(None)
+        } else {
+            Self::arg_str(s).map(Some)
+        }
+    }
+
+    /// Forward a callback error to the originator of the event and return
+    /// the error code for sysrepo.
+    unsafe fn set_callback_error(
+        sess: *mut ffi_sys::sr_session_ctx_t,
+        err: &SrErrorInfo,
+    ) -> i32 {
+        let code = match err.code() {
+            SrError::Ok => SrError::CallbackFailed,
+            code => code,
+        };
+
+        let Some(item) = err.errors().first() else {
+            return code as i32;
+        };
+
+        match (&item.netconf, &item.path) {
+            (Some(netconf), _) => Self::set_netconf_error(sess, netconf),
+            (None, Some(path)) => Self::set_netconf_error(
+                sess,
+                &NetconfError {
+                    error_type: "application".to_string(),
+                    error_tag: "operation-failed".to_string(),
+                    error_path: Some(path.clone()),
+                    error_message: item.message.clone(),
+                    ..Default::default()
+                },
+            ),
+            (None, None) => {
+                if let Ok(message) = str_to_cstring(&item.message) {
+                    ffi_sys::sr_session_set_error_message(
+                        sess,
+                        c"%s".as_ptr(),
+                        message.as_ptr(),
+                    );
+                }
+            }
+        }
+
+        code as i32
+    }
+
+    unsafe fn set_netconf_error(
+        sess: *mut ffi_sys::sr_session_ctx_t,
+        netconf: &NetconfError,
+    ) {
+        let to_cstring = |s: Option<&str>| {
+            s.and_then(|s| str_to_cstring(s).ok()).unwrap_or_default()
+        };
+        let error_type = to_cstring(Some(&netconf.error_type));
+        let error_tag = to_cstring(Some(&netconf.error_tag));
+        let error_app_tag = to_cstring(netconf.error_app_tag.as_deref());
+        let error_path = to_cstring(netconf.error_path.as_deref());
+        let error_message = to_cstring(Some(&netconf.error_message));
+
+        let rc = ffi_sys::sr_session_set_netconf_error(
+            sess,
+            error_type.as_ptr(),
+            error_tag.as_ptr(),
+            error_app_tag.as_ptr(),
+            error_path.as_ptr(),
+            error_message.as_ptr(),
+            0,
+        );
+        if rc != SrError::Ok as i32 {
+            return;
+        }
+
+        // Info elements follow the fixed fields as element/value pairs.
+        for (element, value) in &netconf.error_info {
+            for data in [to_cstring(Some(element)), to_cstring(Some(value))] {
+                let data = data.as_bytes_with_nul();
+                ffi_sys::sr_session_push_error_data(
+                    sess,
+                    data.len() as u32,
+                    data.as_ptr() as *const c_void,
+                );
+            }
+        }
+    }
 }
 
 impl SrSubscription {
@@ -53,31 +387,31 @@ impl SrSubscription {
     ) -> i32
     where
         F: FnMut(
-            SrSession,
+            &mut SrSession,
             u32,
             &str,
             Option<&str>,
             SrEvent,
             u32,
-        ) -> Result<(), SrError>,
+        ) -> Result<(), SrErrorInfo>,
     {
         let callback_ptr = private_data as *mut F;
         let callback = &mut *callback_ptr;
 
-        let mod_name = CStr::from_ptr(mod_name).to_str().unwrap();
-        let path = if path.is_null() {
-            None
-        } else {
-            Some(CStr::from_ptr(path).to_str().unwrap())
-        };
-        let event = SrEvent::try_from(event).expect("Convert error");
-        let sess = SrSession::from(sess, false);
+        Self::guard(sess, || {
+            let mod_name = Self::arg_str(mod_name)?;
+            let path = Self::arg_opt_str(path)?;
+            let event = SrEvent::try_from(event)?;
+            let sess_raw = sess;
+            let mut sess = SrSession::from(sess, false);
 
-        let result = callback(sess, sub_id, mod_name, path, event, request_id);
-        match result {
-            Ok(_) => ffi_sys::sr_error_t_SR_ERR_OK as i32,
-            Err(err) => err as i32,
-        }
+            let result =
+                callback(&mut sess, sub_id, mod_name, path, event, request_id);
+            Ok(match result {
+                Ok(_) => ffi_sys::sr_error_t_SR_ERR_OK as i32,
+                Err(err) => Self::set_callback_error(sess_raw, &err),
+            })
+        })
     }
 
     pub fn on_module_change<F>(
@@ -90,46 +424,88 @@ impl SrSubscription {
     ) -> Result<Self, SrError>
     where
         F: FnMut(
-            SrSession,
-            u32,
-            &str,
-            Option<&str>,
-            SrEvent,
-            u32,
-        ) -> Result<(), SrError>,
+                &mut SrSession,
+                u32,
+                &str,
+                Option<&str>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let mut subscription = Self::from(std::ptr::null_mut());
+        subscription.add_module_change(
+            session,
+            module_name,
+            xpath,
+            module_change_cb,
+            priority,
+            options,
+        )?;
+        Ok(subscription)
+    }
+
+    /// Add a module change subscription to this subscription context, returns
+    /// its sub-id.
+    pub fn add_module_change<F>(
+        &mut self,
+        session: &SrSession,
+        module_name: &str,
+        xpath: Option<&str>,
+        module_change_cb: F,
+        priority: u32,
+        options: ffi_sys::sr_subscr_options_t,
+    ) -> Result<u32, SrError>
+    where
+        F: FnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                Option<&str>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
     {
-        let mut subscription_ctx: *mut ffi_sys::sr_subscription_ctx_t =
-            std::ptr::null_mut();
-        let data = Box::into_raw(Box::new(module_change_cb));
-        let module_name = dup_str(module_name)?;
+        let mut callback = Box::new(module_change_cb);
+        let data = &mut *callback as *mut F;
+        let module_name = str_to_cstring(module_name)?;
         let xpath = match xpath {
-            None => std::ptr::null_mut(),
-            Some(path) => dup_str(path)?,
+            None => None,
+            Some(path) => Some(str_to_cstring(path)?),
         };
 
         let rc = unsafe {
             ffi_sys::sr_module_change_subscribe(
                 session.get_raw_mut(),
-                module_name,
-                xpath,
+                module_name.as_ptr(),
+                xpath.as_ref().map_or(std::ptr::null(), |x| x.as_ptr()),
                 Some(Self::call_module_change::<F>),
                 data as *mut c_void,
                 priority,
                 options,
-                &mut subscription_ctx,
+                &mut self.raw_subscription,
             )
         };
 
         match rc {
-            0 => Ok(Self {
-                raw_subscription: subscription_ctx,
-            }),
+            0 => {
+                let sub_id = self.last_sub_id();
+                self.callbacks.insert(sub_id, callback);
+                self.keep_session(session);
+                Ok(sub_id)
+            }
             rc => Err(SrError::from(rc)),
         }
     }
 }
 
 impl SrSubscription {
+    /// Subscribe operational data provider. The callback borrows the parent
+    /// of the requested nodes from sysrepo, if there is one, and either adds
+    /// the data to it and returns `None`, or returns a new tree.
     pub fn on_oper_get_subscribe<F>(
         session: &SrSession,
         module_name: &str,
@@ -139,38 +515,132 @@ impl SrSubscription {
     ) -> Result<Self, SrError>
     where
         F: for<'a> FnMut(
-            &'a mut SrSession,
-            &'a Context,
-            u32,
-            &'a str,
-            &'a str,
-            Option<&'a str>,
-            u32,
-            Option<DataTree<'a>>,
-        ) -> Result<Option<DataTree<'a>>, SrError>,
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &'a str,
+                &'a str,
+                Option<&'a str>,
+                u32,
+                Option<&'a mut DataTree<'a>>,
+            )
+                -> Result<Option<DataTree<'a>>, SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let mut subscription = Self::from(std::ptr::null_mut());
+        subscription.add_oper_get_subscribe(
+            session,
+            module_name,
+            xpath,
+            callback,
+            opts,
+        )?;
+        Ok(subscription)
+    }
+
+    /// Add an operational data provider subscription to this subscription
+    /// context, returns its sub-id.
+    pub fn add_oper_get_subscribe<F>(
+        &mut self,
+        session: &SrSession,
+        module_name: &str,
+        xpath: &str,
+        callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<u32, SrError>
+    where
+        F: for<'a> FnMut(
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &'a str,
+                &'a str,
+                Option<&'a str>,
+                u32,
+                Option<&'a mut DataTree<'a>>,
+            )
+                -> Result<Option<DataTree<'a>>, SrErrorInfo>
+            + Send
+            + 'static,
     {
-        let mut subscription_ctx: *mut ffi_sys::sr_subscription_ctx_t =
-            unsafe { zeroed::<*mut ffi_sys::sr_subscription_ctx_t>() };
-        let data = Box::into_raw(Box::new(callback));
-        let module_name = dup_str(module_name)?;
-        let path = dup_str(xpath)?;
+        let mut callback = Box::new(callback);
+        let data = &mut *callback as *mut F;
+        let module_name = str_to_cstring(module_name)?;
+        let path = str_to_cstring(xpath)?;
 
         let rc = unsafe {
             ffi_sys::sr_oper_get_subscribe(
                 session.get_raw_mut(),
-                module_name,
-                path,
+                module_name.as_ptr(),
+                path.as_ptr(),
                 Some(Self::oper_get_subscribe_cb::<F>),
                 data as *mut _,
                 opts,
-                &mut subscription_ctx,
+                &mut self.raw_subscription,
             )
         };
 
         match rc {
-            0 => Ok(Self {
-                raw_subscription: subscription_ctx,
-            }),
+            0 => {
+                let sub_id = self.last_sub_id();
+                self.callbacks.insert(sub_id, callback);
+                self.keep_session(session);
+                Ok(sub_id)
+            }
+            rc => Err(SrError::from(rc)),
+        }
+    }
+
+    /// Subscribe operational poll, sysrepo caches the data of the oper get
+    /// subscription on `xpath` for `valid_ms`.
+    pub fn on_oper_poll_subscribe(
+        session: &SrSession,
+        module_name: &str,
+        xpath: &str,
+        valid_ms: u32,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<Self, SrError> {
+        let mut subscription = Self::from(std::ptr::null_mut());
+        subscription.add_oper_poll_subscribe(
+            session,
+            module_name,
+            xpath,
+            valid_ms,
+            opts,
+        )?;
+        Ok(subscription)
+    }
+
+    /// Add an operational poll subscription to this subscription context,
+    /// returns its sub-id.
+    pub fn add_oper_poll_subscribe(
+        &mut self,
+        session: &SrSession,
+        module_name: &str,
+        xpath: &str,
+        valid_ms: u32,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<u32, SrError> {
+        let module_name = str_to_cstring(module_name)?;
+        let path = str_to_cstring(xpath)?;
+
+        let rc = unsafe {
+            ffi_sys::sr_oper_poll_subscribe(
+                session.get_raw_mut(),
+                module_name.as_ptr(),
+                path.as_ptr(),
+                valid_ms,
+                opts,
+                &mut self.raw_subscription,
+            )
+        };
+
+        match rc {
+            0 => {
+                self.keep_session(session);
+                Ok(self.last_sub_id())
+            }
             rc => Err(SrError::from(rc)),
         }
     }
@@ -188,56 +658,60 @@ impl SrSubscription {
     where
         F: for<'a> FnMut(
             &'a mut SrSession,
-            &'a Context,
+            &'a ContextGuard,
             u32,
             &'a str,
             &'a str,
             Option<&'a str>,
             u32,
-            Option<DataTree<'a>>,
-        ) -> Result<Option<DataTree<'a>>, SrError>,
+            Option<&'a mut DataTree<'a>>,
+        ) -> Result<Option<DataTree<'a>>, SrErrorInfo>,
     {
         let callback_ptr = private_data as *mut F;
         let callback = &mut *callback_ptr;
+        let original = *parent;
 
-        let module_name = CStr::from_ptr(module_name).to_str().unwrap();
-        let xpath = CStr::from_ptr(path).to_str().unwrap();
-        let request_xpath = if request_xpath.is_null() {
-            None
-        } else {
-            Some(CStr::from_ptr(request_xpath).to_str().unwrap())
-        };
+        let rc = Self::guard(sess, || {
+            let module_name = Self::arg_str(module_name)?;
+            let xpath = Self::arg_str(path)?;
+            let request_xpath = Self::arg_opt_str(request_xpath)?;
 
-        let mut session = SrSession::from(sess, false);
-        let ctx = ManuallyDrop::new(session.get_context());
+            let mut session = SrSession::from(sess, false);
+            let ctx = session.get_context();
 
-        let node_opt = if (*parent).is_null() {
-            None
-        } else {
-            Some(DataTree::from_raw(&ctx, *parent))
-        };
+            // The parent belongs to sysrepo, the callback only borrows it.
+            let mut node_opt = match (*parent).is_null() {
+                true => None,
+                false => {
+                    Some(ManuallyDrop::new(DataTree::from_raw(&ctx, *parent)))
+                }
+            };
 
-        let res = callback(
-            &mut session,
-            &ctx,
-            sub_id,
-            module_name,
-            xpath,
-            request_xpath,
-            request_id,
-            node_opt,
-        );
+            let res = callback(
+                &mut session,
+                &ctx,
+                sub_id,
+                module_name,
+                xpath,
+                request_xpath,
+                request_id,
+                node_opt.as_deref_mut(),
+            );
 
-        match res {
-            Ok(node) => {
-                match node {
-                    None => *parent = std::ptr::null_mut(),
-                    Some(node) => *parent = node.into_raw(),
+            Ok(match res {
+                Ok(node) => {
+                    if let Some(node) = node {
+                        *parent = node.into_raw();
+                    }
+                    SrError::Ok as i32
                 }
-                SrError::Ok as i32
-            }
-            Err(error) => error as i32,
+                Err(error) => Self::set_callback_error(sess, &error),
+            })
+        });
+        if rc != SrError::Ok as i32 {
+            *parent = original;
         }
+        rc
     }
 }
 
@@ -255,28 +729,41 @@ impl SrSubscription {
         private_data: *mut c_void,
     ) -> i32
     where
-        F: FnMut(SrSession, u32, &str, SrValues, SrEvent, u32) -> SrValues,
+        F: FnMut(
+            &mut SrSession,
+            u32,
+            &str,
+            SrValues,
+            SrEvent,
+            u32,
+        ) -> Result<SrValues, SrErrorInfo>,
     {
         let callback_ptr = private_data as *mut F;
         let callback = &mut *callback_ptr;
 
-        let op_path = CStr::from_ptr(op_path).to_str().unwrap();
-        let inputs = SrValues::from_raw(
-            input as *mut ffi_sys::sr_val_t,
-            input_cnt,
-            false,
-        );
-        let sess = SrSession::from(sess, false);
-        let event = SrEvent::try_from(event).expect("Convert error");
-
-        let sr_outputs =
-            callback(sess, sub_id, op_path, inputs, event, request_id);
-        let (raw, len) = sr_outputs.as_raw();
-        *output = raw;
-        *output_cnt = len;
-        println!("output {:?}", output);
+        Self::guard(sess, || {
+            let op_path = Self::arg_str(op_path)?;
+            let inputs = SrValues::from_raw(
+                input as *mut ffi_sys::sr_val_t,
+                input_cnt,
+                false,
+            );
+            let sess_raw = sess;
+            let mut sess = SrSession::from(sess, false);
+            let event = SrEvent::try_from(event)?;
 
-        SrError::Ok as i32
+            let result =
+                callback(&mut sess, sub_id, op_path, inputs, event, request_id);
+            Ok(match result {
+                Ok(sr_outputs) => {
+                    let (raw, len) = sr_outputs.as_raw();
+                    *output = raw;
+                    *output_cnt = len;
+                    SrError::Ok as i32
+                }
+                Err(err) => Self::set_callback_error(sess_raw, &err),
+            })
+        })
     }
 
     pub fn on_rpc_subscribe<F>(
@@ -287,12 +774,47 @@ impl SrSubscription {
         options: ffi_sys::sr_subscr_options_t,
     ) -> Result<Self, SrError>
     where
-        F: FnMut(SrSession, u32, &str, SrValues, SrEvent, u32) -> SrValues
+        F: FnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                SrValues,
+                SrEvent,
+                u32,
+            ) -> Result<SrValues, SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let mut subscription = Self::from(std::ptr::null_mut());
+        subscription
+            .add_rpc_subscribe(session, xpath, callback, priority, options)?;
+        Ok(subscription)
+    }
+
+    /// Add an RPC subscription to this subscription context, returns its
+    /// sub-id.
+    pub fn add_rpc_subscribe<F>(
+        &mut self,
+        session: &SrSession,
+        xpath: Option<&str>,
+        callback: F,
+        priority: u32,
+        options: ffi_sys::sr_subscr_options_t,
+    ) -> Result<u32, SrError>
+    where
+        F: FnMut(
+                &mut SrSession,
+                u32,
+                &str,
+                SrValues,
+                SrEvent,
+                u32,
+            ) -> Result<SrValues, SrErrorInfo>
+            + Send
             + 'static,
     {
-        let mut subscription_ctx: *mut ffi_sys::sr_subscription_ctx_t =
-            std::ptr::null_mut();
-        let data = Box::into_raw(Box::new(callback));
+        let mut callback = Box::new(callback);
+        let data = &mut *callback as *mut F;
         let xpath = match xpath {
             Some(path) => Some(str_to_cstring(path)?),
             None => None,
@@ -308,14 +830,17 @@ impl SrSubscription {
                 data as *mut _,
                 priority,
                 options,
-                &mut subscription_ctx,
+                &mut self.raw_subscription,
             )
         };
 
         match rc {
-            0 => Ok(Self {
-                raw_subscription: subscription_ctx,
-            }),
+            0 => {
+                let sub_id = self.last_sub_id();
+                self.callbacks.insert(sub_id, callback);
+                self.keep_session(session);
+                Ok(sub_id)
+            }
             rc => Err(SrError::from(rc)),
         }
     }
@@ -333,42 +858,47 @@ impl SrSubscription {
     where
         F: for<'a> FnMut(
             &'a mut SrSession,
-            &'a Context,
+            &'a ContextGuard,
             u32,
             &str,
             &DataTree<'a>,
             &mut DataTree<'a>,
             SrEvent,
             u32,
-        ),
+        ) -> Result<(), SrErrorInfo>,
     {
         let callback_ptr = private_data as *mut F;
         let callback = &mut *callback_ptr;
 
-        let op_path = CStr::from_ptr(op_path).to_str().unwrap();
+        Self::guard(sess, || {
+            let op_path = Self::arg_str(op_path)?;
 
-        let mut sess = SrSession::from(sess, false);
-        let ctx = sess.get_context();
+            let mut sess = SrSession::from(sess, false);
+            let ctx = sess.get_context();
 
-        let inputs =
-            ManuallyDrop::new(DataTree::from_raw(&ctx, input as *mut _));
-        let mut output =
-            ManuallyDrop::new(DataTree::from_raw(&ctx, output as *mut _));
+            let inputs =
+                ManuallyDrop::new(DataTree::from_raw(&ctx, input as *mut _));
+            let mut output =
+                ManuallyDrop::new(DataTree::from_raw(&ctx, output as *mut _));
 
-        let event = SrEvent::try_from(event).expect("Convert error");
+            let event = SrEvent::try_from(event)?;
 
-        callback(
-            &mut sess,
-            &ctx,
-            sub_id,
-            op_path,
-            &inputs,
-            &mut output,
-            event,
-            request_id,
-        );
+            let result = callback(
+                &mut sess,
+                &ctx,
+                sub_id,
+                op_path,
+                &inputs,
+                &mut output,
+                event,
+                request_id,
+            );
 
-        SrError::Ok as i32
+            Ok(match result {
+                Ok(()) => SrError::Ok as i32,
+                Err(err) => Self::set_callback_error(sess.get_raw_mut(), &err),
+            })
+        })
     }
 
     pub fn on_rpc_subscribe_tree<F>(
@@ -380,19 +910,51 @@ impl SrSubscription {
     ) -> Result<Self, SrError>
     where
         F: for<'a> FnMut(
-            &'a mut SrSession,
-            &'a Context,
-            u32,
-            &str,
-            &DataTree<'a>,
-            &mut DataTree<'a>,
-            SrEvent,
-            u32,
-        ),
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &str,
+                &DataTree<'a>,
+                &mut DataTree<'a>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
+    {
+        let mut subscription = Self::from(std::ptr::null_mut());
+        subscription.add_rpc_subscribe_tree(
+            session, xpath, callback, priority, options,
+        )?;
+        Ok(subscription)
+    }
+
+    /// Add an RPC subscription to this subscription context, returns its
+    /// sub-id.
+    pub fn add_rpc_subscribe_tree<F>(
+        &mut self,
+        session: &SrSession,
+        xpath: Option<&str>,
+        callback: F,
+        priority: u32,
+        options: ffi_sys::sr_subscr_options_t,
+    ) -> Result<u32, SrError>
+    where
+        F: for<'a> FnMut(
+                &'a mut SrSession,
+                &'a ContextGuard,
+                u32,
+                &str,
+                &DataTree<'a>,
+                &mut DataTree<'a>,
+                SrEvent,
+                u32,
+            ) -> Result<(), SrErrorInfo>
+            + Send
+            + 'static,
     {
-        let mut subscription_ctx: *mut ffi_sys::sr_subscription_ctx_t =
-            std::ptr::null_mut();
-        let data = Box::into_raw(Box::new(callback));
+        let mut callback = Box::new(callback);
+        let data = &mut *callback as *mut F;
         let xpath = match xpath {
             Some(path) => Some(str_to_cstring(path)?),
             None => None,
@@ -408,14 +970,17 @@ impl SrSubscription {
                 data as *mut _,
                 priority,
                 options,
-                &mut subscription_ctx,
+                &mut self.raw_subscription,
             )
         };
 
         match rc {
-            0 => Ok(Self {
-                raw_subscription: subscription_ctx,
-            }),
+            0 => {
+                let sub_id = self.last_sub_id();
+                self.callbacks.insert(sub_id, callback);
+                self.keep_session(session);
+                Ok(sub_id)
+            }
             rc => Err(SrError::from(rc)),
         }
     }
@@ -427,22 +992,59 @@ impl SrSubscription {
         session: &SrSession,
         module_name: &str,
         xpath: Option<&str>,
-        start_time: Option<*mut ffi_sys::timespec>,
-        stop_time: Option<*mut ffi_sys::timespec>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
         callback: F,
         opts: ffi_sys::sr_subscr_options_t,
     ) -> Result<Self, SrError>
     where
         F: FnMut(
-                SrSession,
+                &mut SrSession,
+                u32,
+                SrNotifType,
+                Option<&str>,
+                SrValues,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
+    {
+        let mut subscription = Self::from(std::ptr::null_mut());
+        subscription.add_notification_subscribe(
+            session,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            callback,
+            opts,
+        )?;
+        Ok(subscription)
+    }
+
+    /// Add an event notification subscription to this subscription context,
+    /// returns its sub-id.
+    pub fn add_notification_subscribe<F>(
+        &mut self,
+        session: &SrSession,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<u32, SrError>
+    where
+        F: FnMut(
+                &mut SrSession,
                 u32,
                 SrNotifType,
                 Option<&str>,
                 SrValues,
-                *mut ffi_sys::timespec,
-            ) + 'static,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
     {
-        let mod_name = dup_str(module_name)?;
+        let mod_name = str_to_cstring(module_name)?;
         let xpath = match xpath {
             Some(path) => Some(str_to_cstring(path)?),
             None => None,
@@ -450,30 +1052,32 @@ impl SrSubscription {
 
         let xpath_ptr = xpath.as_ref().map_or(std::ptr::null(), |x| x.as_ptr());
 
-        let start_time = start_time.unwrap_or(std::ptr::null_mut());
-        let stop_time = stop_time.unwrap_or(std::ptr::null_mut());
+        let start_time = start_time.map(system_time_to_timespec);
+        let stop_time = stop_time.map(system_time_to_timespec);
 
-        let mut subscription_ctx: *mut ffi_sys::sr_subscription_ctx_t =
-            std::ptr::null_mut();
-        let data = Box::into_raw(Box::new(callback));
+        let mut callback = Box::new(callback);
+        let data = &mut *callback as *mut F;
         let rc = unsafe {
             ffi_sys::sr_notif_subscribe(
                 session.get_raw_mut(),
-                mod_name,
+                mod_name.as_ptr(),
                 xpath_ptr,
-                start_time,
-                stop_time,
+                start_time.as_ref().map_or(std::ptr::null(), |t| t),
+                stop_time.as_ref().map_or(std::ptr::null(), |t| t),
                 Some(Self::call_event_notif_cb::<F>),
                 data as *mut _,
                 opts,
-                &mut subscription_ctx,
+                &mut self.raw_subscription,
             )
         };
 
         match rc {
-            0 => Ok(Self {
-                raw_subscription: subscription_ctx,
-            }),
+            0 => {
+                let sub_id = self.last_sub_id();
+                self.callbacks.insert(sub_id, callback);
+                self.keep_session(session);
+                Ok(sub_id)
+            }
             rc => Err(SrError::from(rc)),
         }
     }
@@ -482,21 +1086,57 @@ impl SrSubscription {
         session: &SrSession,
         module_name: &str,
         xpath: Option<&str>,
-        start_time: Option<*mut ffi_sys::timespec>,
-        stop_time: Option<*mut ffi_sys::timespec>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
         callback: F,
         opts: ffi_sys::sr_subscr_options_t,
     ) -> Result<Self, SrError>
     where
         F: FnMut(
-            &SrSession,
-            u32,
-            SrNotifType,
-            &DataTree<'_>,
-            *mut ffi_sys::timespec,
-        ),
+                &SrSession,
+                u32,
+                SrNotifType,
+                &DataTree<'_>,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
+    {
+        let mut subscription = Self::from(std::ptr::null_mut());
+        subscription.add_notification_subscribe_tree(
+            session,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            callback,
+            opts,
+        )?;
+        Ok(subscription)
+    }
+
+    /// Add an event notification subscription to this subscription context,
+    /// returns its sub-id.
+    pub fn add_notification_subscribe_tree<F>(
+        &mut self,
+        session: &SrSession,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        callback: F,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<u32, SrError>
+    where
+        F: FnMut(
+                &SrSession,
+                u32,
+                SrNotifType,
+                &DataTree<'_>,
+                Option<SystemTime>,
+            ) + Send
+            + 'static,
     {
-        let mod_name = dup_str(module_name)?;
+        let mod_name = str_to_cstring(module_name)?;
         let xpath = match xpath {
             Some(path) => Some(str_to_cstring(path)?),
             None => None,
@@ -504,30 +1144,139 @@ impl SrSubscription {
 
         let xpath_ptr = xpath.as_ref().map_or(std::ptr::null(), |x| x.as_ptr());
 
-        let start_time = start_time.unwrap_or(std::ptr::null_mut());
-        let stop_time = stop_time.unwrap_or(std::ptr::null_mut());
+        let start_time = start_time.map(system_time_to_timespec);
+        let stop_time = stop_time.map(system_time_to_timespec);
 
-        let mut subscription_ctx: *mut ffi_sys::sr_subscription_ctx_t =
-            std::ptr::null_mut();
-        let data = Box::into_raw(Box::new(callback));
+        let mut callback = Box::new(callback);
+        let data = &mut *callback as *mut F;
         let rc = unsafe {
             ffi_sys::sr_notif_subscribe_tree(
                 session.get_raw_mut(),
-                mod_name,
+                mod_name.as_ptr(),
                 xpath_ptr,
-                start_time,
-                stop_time,
+                start_time.as_ref().map_or(std::ptr::null(), |t| t),
+                stop_time.as_ref().map_or(std::ptr::null(), |t| t),
                 Some(Self::call_event_notif_tree_cb::<F>),
                 data as *mut _,
                 opts,
-                &mut subscription_ctx,
+                &mut self.raw_subscription,
+            )
+        };
+
+        match rc {
+            0 => {
+                let sub_id = self.last_sub_id();
+                self.callbacks.insert(sub_id, callback);
+                self.keep_session(session);
+                Ok(sub_id)
+            }
+            rc => Err(SrError::from(rc)),
+        }
+    }
+
+    /// Subscribe event notification, the events are sent to the returned
+    /// receiver.
+    pub fn on_notification_stream(
+        session: &SrSession,
+        module_name: &str,
+        xpath: Option<&str>,
+        start_time: Option<SystemTime>,
+        stop_time: Option<SystemTime>,
+        options: SrNotifStreamOptions,
+        opts: ffi_sys::sr_subscr_options_t,
+    ) -> Result<(Self, Receiver<SrNotifEvent>), SrError> {
+        let (sender, receiver) = mpsc::sync_channel(options.capacity);
+        let format = options.format;
+        let block = options.block;
+
+        let subscription = Self::on_notification_subscribe_tree(
+            session,
+            module_name,
+            xpath,
+            start_time,
+            stop_time,
+            move |_sess: &SrSession,
+                  _sub_id: u32,
+                  notif_type: SrNotifType,
+                  notif: &DataTree<'_>,
+                  timestamp: Option<SystemTime>| {
+                let data = match notif.reference() {
+                    Some(_) => notif
+                        .print_string(format, DataPrinterFlags::WITH_SIBLINGS)
+                        .ok(),
+                    None => None,
+                };
+                let event = SrNotifEvent {
+                    notif_type,
+                    timestamp,
+                    format,
+                    data,
+                };
+
+                // A closed receiver or a full buffer drops the event.
+                if block {
+                    let _ = sender.send(event);
+                } else {
+                    let _ = sender.try_send(event);
+                }
+            },
+            opts,
+        )?;
+
+        Ok((subscription, receiver))
+    }
+
+    /// Convert the notification timestamp, sysrepo passes no timestamp for
+    /// some of the events without a notification.
+    unsafe fn notif_timestamp(
+        timestamp: *const ffi_sys::timespec,
+    ) -> Option<SystemTime> {
+        timestamp.as_ref().map(timespec_to_system_time)
+    }
+
+    /// Change the XPath filter of a notification subscription.
+    pub fn modify_notif_xpath(
+        &self,
+        sub_id: u32,
+        xpath: Option<&str>,
+    ) -> Result<(), SrError> {
+        let xpath = match xpath {
+            Some(path) => Some(str_to_cstring(path)?),
+            None => None,
+        };
+        let xpath_ptr = xpath.as_ref().map_or(std::ptr::null(), |x| x.as_ptr());
+
+        let rc = unsafe {
+            ffi_sys::sr_notif_sub_modify_xpath(
+                self.raw_subscription,
+                sub_id,
+                xpath_ptr,
             )
         };
+        match rc {
+            0 => Ok(()),
+            rc => Err(SrError::from(rc)),
+        }
+    }
 
+    /// Change the stop time of a notification subscription, `None` removes
+    /// it.
+    pub fn modify_notif_stop_time(
+        &self,
+        sub_id: u32,
+        stop_time: Option<SystemTime>,
+    ) -> Result<(), SrError> {
+        let stop_time = stop_time.map(system_time_to_timespec);
+
+        let rc = unsafe {
+            ffi_sys::sr_notif_sub_modify_stop_time(
+                self.raw_subscription,
+                sub_id,
+                stop_time.as_ref().map_or(std::ptr::null(), |t| t),
+            )
+        };
         match rc {
-            0 => Ok(Self {
-                raw_subscription: subscription_ctx,
-            }),
+            0 => Ok(()),
             rc => Err(SrError::from(rc)),
         }
     }
@@ -545,22 +1294,23 @@ impl SrSubscription {
             u32,
             SrNotifType,
             &DataTree<'_>,
-            *mut ffi_sys::timespec,
+            Option<SystemTime>,
         ),
     {
         let callback_ptr = private_data as *mut F;
         let callback = &mut *callback_ptr;
 
-        let session = SrSession::from(sess, false);
-        let ctx = session.get_context();
-        let data_tree =
-            ManuallyDrop::new(DataTree::from_raw(&ctx, notif as *mut _));
+        Self::guard(sess, || {
+            let session = SrSession::from(sess, false);
+            let ctx = session.get_context();
+            let data_tree =
+                ManuallyDrop::new(DataTree::from_raw(&ctx, notif as *mut _));
 
-        let notif_type =
-            SrNotifType::try_from(notif_type).map_err(|_| SrError::Internal);
-        if let Ok(notif_type) = notif_type {
+            let notif_type = SrNotifType::try_from(notif_type)?;
+            let timestamp = Self::notif_timestamp(timestamp);
             callback(&session, sub_id, notif_type, &data_tree, timestamp);
-        }
+            Ok(SrError::Ok as i32)
+        });
     }
 
     unsafe extern "C" fn call_event_notif_cb<F>(
@@ -574,39 +1324,80 @@ impl SrSubscription {
         private_data: *mut c_void,
     ) where
         F: FnMut(
-            SrSession,
+            &mut SrSession,
             u32,
             SrNotifType,
             Option<&str>,
             SrValues,
-            *mut ffi_sys::timespec,
+            Option<SystemTime>,
         ),
     {
         let callback_ptr = private_data as *mut F;
         let callback = &mut *callback_ptr;
-        let xpath = if path.is_null() {
-            None
-        } else {
-            Some(CStr::from_ptr(path).to_str().unwrap())
-        };
-        let sr_values = SrValues::from_raw(
-            values as *mut ffi_sys::sr_val_t,
-            values_cnt,
-            false,
-        );
-        let sess = SrSession::from(sess, false);
-        let notif_type =
-            SrNotifType::try_from(notif_type).map_err(|_| SrError::Internal);
-        if let Ok(notif_type) = notif_type {
-            callback(sess, sub_id, notif_type, xpath, sr_values, timestamp);
-        }
+
+        Self::guard(sess, || {
+            let xpath = Self::arg_opt_str(path)?;
+            let sr_values = SrValues::from_raw(
+                values as *mut ffi_sys::sr_val_t,
+                values_cnt,
+                false,
+            );
+            let mut sess = SrSession::from(sess, false);
+            let notif_type = SrNotifType::try_from(notif_type)?;
+            let timestamp = Self::notif_timestamp(timestamp);
+            callback(
+                &mut sess, sub_id, notif_type, xpath, sr_values, timestamp,
+            );
+            Ok(SrError::Ok as i32)
+        });
+    }
+}
+
+/// Get the message of a panic payload.
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
     }
 }
 
 impl Drop for SrSubscription {
     fn drop(&mut self) {
+        // The callbacks are dropped after this, when no event can use them.
         unsafe {
             ffi_sys::sr_unsubscribe(self.raw_subscription);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callback_arguments_reject_invalid_utf8() {
+        let valid = c"/examples:cont";
+        let invalid = b"/examples:\xff\0";
+        unsafe {
+            assert_eq!(
+                SrSubscription::arg_str(valid.as_ptr()),
+                Ok("/examples:cont")
+            );
+            assert!(SrSubscription::arg_str(invalid.as_ptr() as _).is_err());
+            assert_eq!(SrSubscription::arg_opt_str(std::ptr::null()), Ok(None));
+        }
+    }
+
+    #[test]
+    fn panic_message_from_payload() {
+        let payload = panic::catch_unwind(|| panic!("boom")).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "boom");
+
+        let payload =
+            panic::catch_unwind(|| panic!("{}", 42.to_string())).unwrap_err();
+        assert_eq!(panic_message(payload.as_ref()), "42");
+    }
+}
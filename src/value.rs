@@ -1,8 +1,9 @@
-use crate::common::str_to_cstring;
+use crate::common::dup_str;
 use crate::errors::SrError;
 use std::ffi::CStr;
 use std::fmt::{Display, Formatter};
 use sysrepo_sys as sys_ffi;
+use yang3::schema::DataValueType;
 
 #[derive(Debug)]
 pub enum Data {
@@ -271,8 +272,6 @@ impl SrValue {
         dflt: bool,
         owned: bool,
     ) -> Result<Self, SrError> {
-        let xpath = str_to_cstring(&xpath)?;
-        let xpath_ptr: *mut std::os::raw::c_char = xpath.into_raw();
         let val_type = ValType::from(&data);
 
         unsafe {
@@ -280,19 +279,15 @@ impl SrValue {
                 return Err(SrError::Internal);
             }
 
-            (*val).xpath = xpath_ptr;
+            (*val).xpath = dup_str(&xpath)?;
             (*val).dflt = if dflt { 1 } else { 0 };
             match &data {
                 Data::Binary(data) => {
-                    let data = str_to_cstring(data)?;
-                    let data_ptr: *mut std::os::raw::c_char = data.into_raw();
-                    (*val).data.binary_val = data_ptr;
+                    (*val).data.binary_val = dup_str(data)?;
                     (*val).type_ = sys_ffi::sr_val_type_t_SR_BINARY_T;
                 }
                 Data::Bits(data) => {
-                    let data = str_to_cstring(data)?;
-                    let data_ptr: *mut std::os::raw::c_char = data.into_raw();
-                    (*val).data.bits_val = data_ptr;
+                    (*val).data.bits_val = dup_str(data)?;
                     (*val).type_ = sys_ffi::sr_val_type_t_SR_BITS_T;
                 }
                 Data::Boolean(data) => {
@@ -305,11 +300,20 @@ impl SrValue {
                 }
                 Data::Empty => {
                     (*val).data.string_val = std::ptr::null_mut();
-                    (*val).type_ = sys_ffi::sr_val_type_t_SR_STRING_T;
+                    (*val).type_ = sys_ffi::sr_val_type_t_SR_LEAF_EMPTY_T;
+                }
+                Data::Enumeration(data) => {
+                    (*val).data.enum_val = dup_str(data)?;
+                    (*val).type_ = sys_ffi::sr_val_type_t_SR_ENUM_T;
+                }
+                Data::IdentityRef(data) => {
+                    (*val).data.identityref_val = dup_str(data)?;
+                    (*val).type_ = sys_ffi::sr_val_type_t_SR_IDENTITYREF_T;
+                }
+                Data::InstanceIdentifier(data) => {
+                    (*val).data.instanceid_val = dup_str(data)?;
+                    (*val).type_ = sys_ffi::sr_val_type_t_SR_INSTANCEID_T;
                 }
-                Data::Enumeration(_data) => {}
-                Data::IdentityRef(_) => {}
-                Data::InstanceIdentifier(_) => {}
                 Data::Int8(data) => {
                     (*val).data.int8_val = *data;
                     (*val).type_ = sys_ffi::sr_val_type_t_SR_INT8_T;
@@ -328,9 +332,7 @@ impl SrValue {
                 }
                 Data::LeafRef() => {}
                 Data::String(data) => {
-                    let data = str_to_cstring(data)?;
-                    let data_ptr: *mut std::os::raw::c_char = data.into_raw();
-                    (*val).data.bits_val = data_ptr;
+                    (*val).data.string_val = dup_str(data)?;
                     (*val).type_ = sys_ffi::sr_val_type_t_SR_STRING_T;
                 }
                 Data::UInt8(data) => {
@@ -349,7 +351,52 @@ impl SrValue {
                     (*val).data.uint64_val = *data;
                     (*val).type_ = sys_ffi::sr_val_type_t_SR_UINT64_T;
                 }
-                Data::Union(_data) => {}
+                Data::Union(data) => match data {
+                    UnionData::Int8(data) => {
+                        (*val).data.int8_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_INT8_T;
+                    }
+                    UnionData::Int16(data) => {
+                        (*val).data.int16_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_INT16_T;
+                    }
+                    UnionData::Int32(data) => {
+                        (*val).data.int32_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_INT32_T;
+                    }
+                    UnionData::Int64(data) => {
+                        (*val).data.int64_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_INT64_T;
+                    }
+                    UnionData::String(data) => {
+                        (*val).data.string_val = dup_str(data)?;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_STRING_T;
+                    }
+                    UnionData::UInt8(data) => {
+                        (*val).data.uint8_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_UINT8_T;
+                    }
+                    UnionData::UInt16(data) => {
+                        (*val).data.uint16_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_UINT16_T;
+                    }
+                    UnionData::UInt32(data) => {
+                        (*val).data.uint32_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_UINT32_T;
+                    }
+                    UnionData::UInt64(data) => {
+                        (*val).data.uint64_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_UINT64_T;
+                    }
+                    UnionData::Boolean(data) => {
+                        (*val).data.bool_val = if *data { 1 } else { 0 };
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_BOOL_T;
+                    }
+                    UnionData::Decimal64(data) => {
+                        (*val).data.decimal64_val = *data;
+                        (*val).type_ = sys_ffi::sr_val_type_t_SR_DECIMAL64_T;
+                    }
+                },
             }
         }
 
@@ -360,6 +407,36 @@ impl SrValue {
             owned,
         })
     }
+
+    /// Check if the data can be stored in a leaf of the given base type.
+    pub fn matches_type(data: &Data, base_type: DataValueType) -> bool {
+        match base_type {
+            // Resolved by libyang against the target or member types.
+            DataValueType::LeafRef | DataValueType::Union => {
+                !matches!(data, Data::LeafRef())
+            }
+            base_type => matches!(
+                (data, base_type),
+                (Data::Binary(_), DataValueType::Binary)
+                    | (Data::Bits(_), DataValueType::Bits)
+                    | (Data::Boolean(_), DataValueType::Bool)
+                    | (Data::Decimal64(_), DataValueType::Dec64)
+                    | (Data::Empty, DataValueType::Empty)
+                    | (Data::Enumeration(_), DataValueType::Enum)
+                    | (Data::IdentityRef(_), DataValueType::IdentityRef)
+                    | (Data::InstanceIdentifier(_), DataValueType::InstanceId)
+                    | (Data::Int8(_), DataValueType::Int8)
+                    | (Data::Int16(_), DataValueType::Int16)
+                    | (Data::Int32(_), DataValueType::Int32)
+                    | (Data::Int64(_), DataValueType::Int64)
+                    | (Data::String(_), DataValueType::String)
+                    | (Data::UInt8(_), DataValueType::Uint8)
+                    | (Data::UInt16(_), DataValueType::Uint16)
+                    | (Data::UInt32(_), DataValueType::Uint32)
+                    | (Data::UInt64(_), DataValueType::Uint64)
+            ),
+        }
+    }
 }
 
 impl SrValue {
@@ -419,6 +496,23 @@ mod test {
         assert_eq!(value.val_type, ValType::String);
     }
 
+    #[test]
+    fn data_matches_leaf_type() {
+        assert!(SrValue::matches_type(&Data::Int32(1), DataValueType::Int32));
+        assert!(!SrValue::matches_type(
+            &Data::String("1".to_string()),
+            DataValueType::Int32
+        ));
+        assert!(SrValue::matches_type(
+            &Data::String("1".to_string()),
+            DataValueType::Union
+        ));
+        assert!(!SrValue::matches_type(
+            &Data::LeafRef(),
+            DataValueType::LeafRef
+        ));
+    }
+
     fn string_to_mut_c_char(
         s: &str,
     ) -> Result<*mut std::os::raw::c_char, std::ffi::NulError> {
@@ -1,2 +1,4 @@
 #include <sysrepo.h>
+#include <sysrepo/netconf_acm.h>
+#include <sysrepo/plugins_datastore.h>
 #include <sysrepo/xpath.h>
\ No newline at end of file
@@ -0,0 +1,230 @@
+#![cfg(feature = "tokio")]
+
+use crate::common::Setup;
+use std::ops::{AddAssign, DerefMut};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use sysrepo::connection::{ConnectionOptions, SrConnection};
+use sysrepo::enums::{SrDatastore, SrNotifType};
+use sysrepo::errors::{SrError, SrErrorInfo};
+use sysrepo::session::{SrEvent, SrSession};
+use sysrepo::subscription::SrSubscription;
+use sysrepo::value::Data;
+use sysrepo::values::SrValues;
+
+pub mod common;
+
+// The callbacks do not run on a runtime worker, so the current thread
+// runtime is enough.
+#[tokio::test(flavor = "current_thread")]
+async fn test_async_subscriptions() {
+    let _setup = Setup::setup_example();
+
+    test_module_change_async().await;
+    test_rpc_subscribe_async().await;
+    test_notif_subscribe_async().await;
+}
+
+#[test]
+fn test_async_subscription_without_runtime() {
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+    let session = connection.start_session(SrDatastore::Running).unwrap();
+
+    // There is no runtime to run the callbacks on.
+    let sub = SrSubscription::on_module_change_async(
+        session,
+        "examples",
+        None,
+        async move |_session: &mut SrSession,
+                    _sub_id: u32,
+                    _module_name: &str,
+                    _xpath: Option<&str>,
+                    _event: SrEvent,
+                    _request_id: u32|
+                    -> Result<(), SrErrorInfo> { Ok(()) },
+        0,
+        0,
+    );
+    assert!(sub.is_err_and(|err| err == SrError::Unsupported));
+}
+
+async fn test_module_change_async() {
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+    let session = connection.start_session(SrDatastore::Running).unwrap();
+    let check = Arc::new(Mutex::new(0));
+    let change_cb_value = check.clone();
+
+    let sub = session
+        .on_module_change_subscribe_async(
+            "examples",
+            Some("/examples:cont/l"),
+            async move |_session: &mut SrSession,
+                        _sub_id: u32,
+                        _module_name: &str,
+                        _xpath: Option<&str>,
+                        _event: SrEvent,
+                        _request_id: u32|
+                        -> Result<(), SrErrorInfo> {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                change_cb_value.lock().unwrap().deref_mut().add_assign(1);
+                Ok(())
+            },
+            0,
+            0,
+        )
+        .unwrap();
+    let task = tokio::spawn(sub.run());
+
+    tokio::task::spawn_blocking(|| {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        session
+            .set_item_str("/examples:cont/l", Some("async"), None, 0)
+            .unwrap();
+        session.apply_changes(None).unwrap();
+    })
+    .await
+    .unwrap();
+
+    // The done event is not waited for by the writer.
+    for _ in 0..50 {
+        if *check.lock().unwrap() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!task.is_finished());
+    task.abort();
+
+    // Change is called 2 times
+    assert_eq!(*check.lock().unwrap(), 2);
+}
+
+async fn test_rpc_subscribe_async() {
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_Operational).unwrap();
+    let session = connection.start_session(SrDatastore::Operational).unwrap();
+
+    let sub = session
+        .on_rpc_subscribe_async(
+            Some("/examples:oper"),
+            async |_session: &mut SrSession,
+                   _sub_id: u32,
+                   _op_path: &str,
+                   _inputs: SrValues,
+                   _event: SrEvent,
+                   _request_id: u32|
+                   -> Result<SrValues, SrErrorInfo> {
+                tokio::task::yield_now().await;
+                let mut output = SrValues::new(1, false);
+                output
+                    .add_value(
+                        0,
+                        "/examples:oper/ret".to_string(),
+                        Data::Int64(123),
+                        false,
+                    )
+                    .unwrap();
+                Ok(output)
+            },
+            0,
+            0,
+        )
+        .unwrap();
+    let task = tokio::spawn(sub.run());
+
+    let val = tokio::task::spawn_blocking(|| {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Operational)
+                .unwrap();
+        let session =
+            connection.start_session(SrDatastore::Operational).unwrap();
+        let mut input = SrValues::new(2, false);
+        input
+            .add_value(
+                0,
+                "/examples:oper/arg".to_string(),
+                Data::String("123".to_string()),
+                false,
+            )
+            .unwrap();
+        input
+            .add_value(
+                1,
+                "/examples:oper/arg2".to_string(),
+                Data::Int8(123),
+                false,
+            )
+            .unwrap();
+        let data = session
+            .rpc_send("/examples:oper", Some(input), None)
+            .unwrap();
+        match data.get_value_mut(0).unwrap().data() {
+            Data::Int64(val) => *val,
+            _ => panic!("Expected an int64 output"),
+        }
+    })
+    .await
+    .unwrap();
+    task.abort();
+
+    assert_eq!(val, 123);
+}
+
+async fn test_notif_subscribe_async() {
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+    let session = connection.start_session(SrDatastore::Running).unwrap();
+    let check = Arc::new(Mutex::new(0));
+    let notif_cb_value = check.clone();
+
+    let sub = session
+        .on_notif_subscribe_async(
+            "examples",
+            Some("/examples:notif"),
+            None,
+            None,
+            async move |_session: &mut SrSession,
+                        _sub_id: u32,
+                        notif_type: SrNotifType,
+                        xpath: Option<&str>,
+                        values: SrValues,
+                        _timestamp: Option<SystemTime>| {
+                tokio::task::yield_now().await;
+                if notif_type == SrNotifType::Realtime {
+                    assert_eq!(xpath, Some("/examples:notif"));
+                    assert_eq!(values.len(), 1);
+                    notif_cb_value.lock().unwrap().deref_mut().add_assign(1);
+                }
+            },
+            0,
+        )
+        .unwrap();
+    let task = tokio::spawn(sub.run());
+
+    tokio::task::spawn_blocking(|| {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let mut values = SrValues::new(1, false);
+        values
+            .add_value(
+                0,
+                "/examples:notif/val".to_string(),
+                Data::Decimal64(123.0),
+                false,
+            )
+            .unwrap();
+        session
+            .notif_send("/examples:notif", &values, 0, 1)
+            .unwrap();
+    })
+    .await
+    .unwrap();
+    task.abort();
+
+    assert_eq!(*check.lock().unwrap(), 1);
+}
@@ -1,8 +1,15 @@
 use std::path::Path;
-use sysrepo::connection::{ConnectionOptions, SrConnection};
+use sysrepo::connection::{ConnectionOptions, SrConnection, SrInitialData};
+use sysrepo::enums::{SrDatastore, SrGetOptions};
+use sysrepo::errors::SrError;
+use sysrepo::value::Data;
+use yang3::context::{Context, ContextFlags};
+use yang3::data::{DataFormat, DataTree};
 
 pub mod common;
 
+const FIXTURE: &str = "./assets/yang/fixture-test.yang";
+
 #[test]
 fn install_and_remove_module_successful() {
     let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
@@ -43,3 +50,298 @@ fn install_and_remove_module_successful() {
         assert!(remove.is_ok());
     }
 }
+
+#[test]
+fn manage_module_successful() {
+    let yang = "./assets/yang/";
+    let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
+        .expect("Should be Ok");
+    let install = connection.install_modules(
+        &[
+            (
+                Path::new("./assets/yang/manage-test@2024-01-01.yang"),
+                Some(&["extra"]),
+            ),
+            (Path::new("./assets/yang/manage-import.yang"), None),
+        ],
+        Some(yang),
+    );
+    assert!(install.is_ok());
+
+    {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        assert!(session
+            .set_item_str("/manage-test:cont/extra", Some("on"), None, 0)
+            .is_ok());
+        assert!(session
+            .set_item_str(
+                "/manage-test:cont/manage-import:imported",
+                Some("1"),
+                None,
+                0
+            )
+            .is_ok());
+        session.discard_changes().unwrap();
+    }
+
+    // Features.
+    assert!(connection
+        .disable_module_feature("manage-test", "extra")
+        .is_ok());
+    {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        assert!(session
+            .set_item_str("/manage-test:cont/extra", Some("on"), None, 0)
+            .is_err());
+    }
+    assert!(connection
+        .enable_module_feature("manage-test", "extra")
+        .is_ok());
+    assert!(connection
+        .enable_module_feature("manage-test", "missing")
+        .is_err());
+
+    // Update to the newer revision.
+    let update = connection.update_module(
+        Path::new("./assets/yang/manage-test@2024-06-01.yang"),
+        Some(yang),
+    );
+    assert!(update.is_ok());
+    {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        assert!(session
+            .set_item_str("/manage-test:cont/added", Some("new"), None, 0)
+            .is_ok());
+        session.discard_changes().unwrap();
+    }
+
+    // Replay.
+    assert!(!connection.module_replay_support("manage-test").unwrap());
+    assert!(connection
+        .set_module_replay_support(Some("manage-test"), true)
+        .is_ok());
+    assert!(connection.module_replay_support("manage-test").unwrap());
+    assert!(connection
+        .set_module_replay_support(Some("manage-test"), false)
+        .is_ok());
+    assert!(!connection.module_replay_support("manage-test").unwrap());
+
+    // Access.
+    let access = connection
+        .get_module_ds_access("manage-test", SrDatastore::Running)
+        .unwrap();
+    assert!(connection
+        .set_module_ds_access(
+            "manage-test",
+            SrDatastore::Running,
+            None,
+            None,
+            Some(0o600),
+        )
+        .is_ok());
+    let changed = connection
+        .get_module_ds_access("manage-test", SrDatastore::Running)
+        .unwrap();
+    assert_eq!(changed.perm, 0o600);
+    assert_eq!(changed.owner, access.owner);
+    assert_eq!(changed.group, access.group);
+
+    assert!(connection.remove_module("manage-import", false).is_ok());
+    assert!(connection.remove_module("manage-test", false).is_ok());
+}
+
+// The tests share one module, so they run in order.
+#[test]
+fn install_fixture_module_successful() {
+    install_module_with_data();
+    install_module_after_context_guards_dropped();
+    install_module_after_data_dropped();
+}
+
+fn install_module_with_data() {
+    let module = Path::new(FIXTURE);
+    let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
+        .expect("Should be Ok");
+
+    let install = connection.install_module_with_data(
+        module,
+        None,
+        None,
+        SrInitialData::String(
+            r#"{"fixture-test:cont":{"l":"initial"}}"#,
+            DataFormat::JSON,
+        ),
+    );
+    assert!(install.is_ok());
+    assert_eq!(leaf_value(SrDatastore::Startup), "initial");
+    assert_eq!(leaf_value(SrDatastore::Running), "initial");
+
+    // Factory-default data from a tree.
+    let mut ctx = Context::new(ContextFlags::NO_YANGLIBRARY).unwrap();
+    ctx.set_searchdir("./assets/yang").unwrap();
+    ctx.load_module("fixture-test", None, &[]).unwrap();
+    let mut tree = DataTree::new(&ctx);
+    tree.new_path("/fixture-test:cont/l", Some("factory"), false)
+        .unwrap();
+    let factory = connection.install_factory_config(SrInitialData::Tree(&tree));
+    assert!(factory.is_ok());
+    assert_eq!(leaf_value(SrDatastore::FactoryDefault), "factory");
+
+    assert!(connection.remove_module("fixture-test", false).is_ok());
+
+    // Initial data from a file.
+    let install = connection.install_module_with_data(
+        module,
+        None,
+        None,
+        SrInitialData::File(Path::new("./assets/data/initial-data.json")),
+    );
+    assert!(install.is_ok());
+    assert_eq!(leaf_value(SrDatastore::Startup), "from-file");
+
+    assert!(connection.remove_module("fixture-test", false).is_ok());
+}
+
+fn install_module_after_context_guards_dropped() {
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_Running)
+            .expect("Should be Ok");
+    {
+        let _ctx = connection.get_context();
+        let _other_ctx = connection.get_context();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let _session_ctx = session.get_context();
+    }
+
+    let install = connection.install_module(Path::new(FIXTURE), None, None);
+    assert!(install.is_ok());
+    {
+        let ctx = connection.get_context();
+        assert!(ctx.get_module_latest("fixture-test").is_some());
+    }
+    assert!(connection.remove_module("fixture-test", false).is_ok());
+}
+
+fn install_module_after_data_dropped() {
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_Running)
+            .expect("Should be Ok");
+    {
+        let session =
+            connection.start_session(SrDatastore::Operational).unwrap();
+        let ctx = session.get_context();
+        let data = session
+            .get_data(
+                &ctx,
+                "/ietf-yang-library:yang-library",
+                0,
+                None,
+                SrGetOptions::SR_OPER_DEFAULT,
+            )
+            .unwrap();
+        assert!(data.reference().is_some());
+    }
+
+    // The dropped data released the context.
+    let install = connection.install_module(Path::new(FIXTURE), None, None);
+    assert!(install.is_ok());
+    assert!(connection.remove_module("fixture-test", false).is_ok());
+}
+
+#[test]
+fn validate_successful() {
+    let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
+        .expect("Should be Ok");
+    let install = connection.install_module(
+        Path::new("./assets/yang/validate-test.yang"),
+        None,
+        None,
+    );
+    assert!(install.is_ok());
+
+    // Pending changes with a dangling leafref and a missing mandatory leaf.
+    {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        session
+            .set_item_str("/validate-test:cont/ref", Some("missing"), None, 0)
+            .unwrap();
+        session
+            .set_item_str("/validate-test:cont/pres", None, None, 0)
+            .unwrap();
+        let err = session.validate(Some("validate-test"), None).unwrap_err();
+        assert_eq!(err, SrError::ValidationFailed);
+        assert!(err.errors().len() >= 2);
+        assert!(err.errors().iter().all(|err| err.path.is_some()));
+
+        session
+            .set_item_str(
+                "/validate-test:cont/target",
+                Some("missing"),
+                None,
+                0,
+            )
+            .unwrap();
+        session
+            .set_item_str(
+                "/validate-test:cont/pres/mandatory",
+                Some("on"),
+                None,
+                0,
+            )
+            .unwrap();
+        assert!(session.validate(None, None).is_ok());
+        session.discard_changes().unwrap();
+    }
+
+    // Offline data tree.
+    let mut ctx = Context::new(ContextFlags::NO_YANGLIBRARY).unwrap();
+    ctx.set_searchdir("./assets/yang").unwrap();
+    ctx.load_module("validate-test", None, &[]).unwrap();
+    let mut tree = DataTree::new(&ctx);
+    for (path, value) in [
+        ("/validate-test:cont/ref", Some("missing")),
+        ("/validate-test:cont/pres", None),
+    ] {
+        tree.new_path(path, value, false).unwrap();
+    }
+    let err = connection
+        .validate_tree(&tree, SrDatastore::Running)
+        .unwrap_err();
+    assert!(err.errors().len() >= 2);
+    assert!(err
+        .errors()
+        .iter()
+        .any(|err| err.path.as_deref() == Some("/validate-test:cont/ref")));
+
+    for (path, value) in [
+        ("/validate-test:cont/target", "missing"),
+        ("/validate-test:cont/pres/mandatory", "on"),
+    ] {
+        tree.new_path(path, Some(value), false).unwrap();
+    }
+    assert!(connection
+        .validate_tree(&tree, SrDatastore::Running)
+        .is_ok());
+
+    assert!(connection.remove_module("validate-test", false).is_ok());
+}
+
+fn leaf_value(datastore: SrDatastore) -> String {
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+    let session = connection.start_session(datastore).unwrap();
+    let values = session.get_items("/fixture-test:cont/l", None, 0).unwrap();
+    assert_eq!(values.len(), 1);
+    match values.get_value_mut(0).unwrap().data() {
+        Data::String(val) => val.clone(),
+        _ => panic!("Wrong data type"),
+    }
+}
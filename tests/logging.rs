@@ -0,0 +1,132 @@
+#![cfg(feature = "tracing")]
+
+use crate::common::Setup;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use sysrepo::connection::{ConnectionOptions, SrConnection};
+use sysrepo::enums::{SrDatastore, SrLogLevel};
+use sysrepo::logging::{log_tracing, log_tracing_stop};
+use tracing::field::{Field, Visit};
+use tracing::{span, Event, Level, Metadata, Subscriber};
+
+pub mod common;
+
+const LEAF: &str = "/test_module:cont/l";
+
+#[derive(Debug)]
+struct Record {
+    target: String,
+    level: Level,
+    fields: HashMap<String, String>,
+    thread: ThreadId,
+}
+
+/// Subscriber collecting all the events.
+#[derive(Default)]
+struct Collector(Arc<Mutex<Vec<Record>>>);
+
+#[derive(Default)]
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{value:?}"));
+    }
+}
+
+impl Subscriber for Collector {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        self.0.lock().unwrap().push(Record {
+            target: event.metadata().target().to_string(),
+            level: *event.metadata().level(),
+            fields: fields.0,
+            thread: thread::current().id(),
+        });
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
+
+fn set_invalid(connection: &SrConnection) {
+    let mut session = connection.new_session(SrDatastore::Running).unwrap();
+    assert!(session
+        .set_item_str(LEAF, Some("more than 10"), None, 0)
+        .is_err());
+}
+
+#[test]
+fn test_logging() {
+    let _setup = Setup::setup_test_module();
+    let connection =
+        SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+
+    let collector = Collector::default();
+    let records = collector.0.clone();
+    tracing::subscriber::with_default(collector, || {
+        log_tracing(SrLogLevel::Warn)
+    });
+
+    set_invalid(&connection);
+    {
+        let records = records.lock().unwrap();
+        let libyang = records
+            .iter()
+            .find(|record| record.target == "libyang")
+            .expect("libyang event");
+        assert_eq!(libyang.level, Level::ERROR);
+        assert!(libyang.fields.contains_key("message"));
+        assert!(libyang.fields["path"].contains(LEAF));
+
+        let sysrepo = records
+            .iter()
+            .find(|record| record.target == "sysrepo")
+            .expect("sysrepo event");
+        assert!(sysrepo.fields.contains_key("message"));
+        assert!(records.iter().all(|record| record.level <= Level::WARN));
+    }
+
+    // Messages from other threads go to the same subscriber.
+    records.lock().unwrap().clear();
+    let thread = thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                set_invalid(&connection);
+                thread::current().id()
+            })
+            .join()
+            .unwrap()
+    });
+    let records_len = {
+        let records = records.lock().unwrap();
+        assert!(!records.is_empty());
+        assert!(records.iter().all(|record| record.thread == thread));
+        records.len()
+    };
+
+    log_tracing_stop();
+    set_invalid(&connection);
+    assert_eq!(records.lock().unwrap().len(), records_len);
+}
@@ -1,14 +1,14 @@
 use crate::common::Setup;
-use std::mem::ManuallyDrop;
-use std::time::Duration;
-use sysrepo::connection::{ConnectionOptions, SrConnection};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use sysrepo::confirmed_commit::SrConfirmedCommit;
+use sysrepo::connection::{ConnectionOptions, ContextGuard, SrConnection};
 use sysrepo::enums::{
     DefaultOperation, SrDatastore, SrEditFlag, SrGetOptions, SrLogLevel,
 };
 use sysrepo::errors::SrError;
 use sysrepo::session::SrSession;
 use sysrepo::{log_stderr, value};
-use yang3::context::Context;
 use yang3::data::{Data, DataFormat, DataPrinterFlags, DataTree};
 use yang3::schema::{DataValue, SchemaPathFormat};
 
@@ -27,6 +27,13 @@ fn test_session() {
     test_replace_config_with_none();
     test_replace_config_with_config();
     test_copy_config_from_startup_to_running();
+    test_lock_contention();
+    test_set_item_typed();
+    test_error_details();
+    test_oper_push_data();
+    test_shared_handles();
+    test_nacm();
+    test_confirmed_commit();
 }
 
 fn test_data_manipulation() {
@@ -528,8 +535,8 @@ fn test_pending_changes() {
 
 fn prepare_test_replace_config<'a>(
     session: &mut SrSession,
-    ctx: &'a ManuallyDrop<Context>,
-) -> ManuallyDrop<DataTree<'a>> {
+    ctx: &'a ContextGuard,
+) -> DataTree<'a> {
     assert!(session
         .get_data(&ctx, LEAF, 0, None, SrGetOptions::SR_OPER_DEFAULT)
         .is_err());
@@ -540,7 +547,8 @@ fn prepare_test_replace_config<'a>(
     let conf =
         session.get_data(&ctx, "/*", 0, None, SrGetOptions::SR_OPER_DEFAULT);
     assert!(conf.is_ok());
-    let conf = conf.unwrap();
+    // Outlives the data returned by sysrepo.
+    let conf = conf.unwrap().into_owned().unwrap();
 
     session.set_item_str(LEAF, Some("123"), None, 0).unwrap();
     session.apply_changes(None).unwrap();
@@ -551,7 +559,7 @@ fn prepare_test_replace_config<'a>(
     let data = data.reference().unwrap().value();
     assert_eq!(data, Some(DataValue::Int32(123)));
 
-    ManuallyDrop::new(conf)
+    conf
 }
 
 fn test_replace_config_with_none() {
@@ -591,7 +599,7 @@ fn test_replace_config_with_config() {
     let conf = prepare_test_replace_config(session, &ctx);
 
     assert!(session
-        .replace_config(Some(&conf), Some("test_module"), None)
+        .replace_config(Some(conf), Some("test_module"), None)
         .is_ok());
     let data = session
         .get_data(&ctx, LEAF, 0, None, SrGetOptions::SR_OPER_DEFAULT)
@@ -624,3 +632,408 @@ fn test_copy_config_from_startup_to_running() {
     let value = data.reference().unwrap().value();
     assert_eq!(value, Some(DataValue::Int32(1)));
 }
+
+fn test_lock_contention() {
+    log_stderr(SrLogLevel::Error);
+    let _setup = Setup::setup_test_module();
+
+    let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
+        .expect("connect");
+    let mut first = connection
+        .new_session(SrDatastore::Running)
+        .expect("session");
+    let mut second = connection
+        .new_session(SrDatastore::Running)
+        .expect("session");
+
+    let info = first
+        .lock_info(SrDatastore::Running, Some("test_module"))
+        .unwrap();
+    assert!(!info.is_locked);
+
+    {
+        let mut guard = first
+            .lock(Some("test_module"), None)
+            .expect("First lock should succeed");
+
+        let info = second
+            .lock_info(SrDatastore::Running, Some("test_module"))
+            .unwrap();
+        assert!(info.is_locked);
+        assert_eq!(info.sid, guard.get_id());
+        assert!(info.timestamp.is_some());
+
+        assert!(second
+            .lock(Some("test_module"), None)
+            .is_err_and(|e| e == SrError::Locked));
+
+        guard.set_item_str(LEAF, Some("1"), None, 0).unwrap();
+        guard.apply_changes(None).unwrap();
+    }
+
+    let info = second
+        .lock_info(SrDatastore::Running, Some("test_module"))
+        .unwrap();
+    assert!(!info.is_locked);
+
+    // The locked datastore is unlocked even after switching the session.
+    {
+        let guard = second.lock(Some("test_module"), None).unwrap();
+        guard.switch_datastore(SrDatastore::Startup).unwrap();
+    }
+    assert_eq!(second.active_datastore(), SrDatastore::Startup);
+    let info = first
+        .lock_info(SrDatastore::Running, Some("test_module"))
+        .unwrap();
+    assert!(!info.is_locked);
+    second.switch_datastore(SrDatastore::Running).unwrap();
+
+    let guard = second.lock(Some("test_module"), None);
+    assert!(guard.is_ok());
+}
+
+fn test_set_item_typed() {
+    log_stderr(SrLogLevel::Error);
+    let _setup = Setup::setup_test_module();
+
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_Running)
+            .expect("connect");
+    let session = connection
+        .start_session(SrDatastore::Running)
+        .expect("session");
+    let ctx = session.get_context();
+
+    assert!(session
+        .set_item(LEAF, value::Data::Int32(42), None, 0)
+        .is_ok());
+    assert!(session
+        .set_item(
+            "/test_module:cont/sub/test-list[name='a']/val",
+            value::Data::String("b".to_string()),
+            None,
+            0
+        )
+        .is_ok());
+    session.apply_changes(None).unwrap();
+
+    let data = session
+        .get_data(&ctx, LEAF, 0, None, SrGetOptions::SR_OPER_DEFAULT)
+        .unwrap();
+    assert_eq!(
+        data.reference().unwrap().value(),
+        Some(DataValue::Int32(42))
+    );
+
+    assert!(session
+        .set_item(LEAF, value::Data::String("42".to_string()), None, 0)
+        .is_err_and(|e| e == SrError::InvalArg));
+    assert!(session
+        .set_item(LEAF, value::Data::Int64(42), None, 0)
+        .is_err_and(|e| e == SrError::InvalArg));
+    assert!(session
+        .set_item("/test_module:cont", value::Data::Int32(42), None, 0)
+        .is_err_and(|e| e == SrError::InvalArg));
+}
+
+fn test_error_details() {
+    log_stderr(SrLogLevel::Error);
+    let _setup = Setup::setup_test_module();
+
+    let mut connection =
+        SrConnection::new(ConnectionOptions::Datastore_Running)
+            .expect("connect");
+    let session = connection
+        .start_session(SrDatastore::Running)
+        .expect("session");
+
+    let err = session
+        .set_item_str("/test_module:cont/l", Some("more than 10"), None, 0)
+        .expect_err("Value exceeds the length restriction");
+    assert_eq!(err, SrError::Ly);
+    assert!(!err.errors().is_empty());
+    assert!(err.message().is_some_and(|msg| !msg.is_empty()));
+    assert!(err.path().is_some_and(|path| path.contains("test_module:")));
+    assert!(err.to_string().starts_with("Lib Yang: "));
+
+    let err = session
+        .set_item(LEAF, value::Data::Boolean(true), None, 0)
+        .expect_err("Type mismatch");
+    assert_eq!(err.code(), SrError::InvalArg);
+    assert!(err.message().is_some_and(|msg| msg.contains(LEAF)));
+}
+
+fn test_oper_push_data() {
+    log_stderr(SrLogLevel::Error);
+    let _setup = Setup::setup_test_module();
+    const STATE_LEAF: &str = "/test_module:stateLeaf";
+
+    let mut reader_connection =
+        SrConnection::new(ConnectionOptions::Datastore_Running)
+            .expect("connect");
+    let reader = reader_connection
+        .start_session(SrDatastore::Operational)
+        .expect("session");
+    let mut read = || reader.get_items(STATE_LEAF, None, 0).unwrap().len();
+
+    {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running)
+                .expect("connect");
+        let session = connection
+            .start_session(SrDatastore::Operational)
+            .expect("session");
+        let ctx = session.get_context();
+
+        session
+            .set_item_str(STATE_LEAF, Some("7"), None, 0)
+            .unwrap();
+        session.apply_changes(None).unwrap();
+        assert_eq!(read(), 1);
+
+        let changes = session.get_oper_changes(&ctx, Some("test_module"));
+        let changes = changes.expect("push data");
+        let node = changes.find_path(STATE_LEAF).expect("stateLeaf");
+        assert_eq!(node.value(), Some(DataValue::Int32(7)));
+
+        // Delete the pushed node.
+        session.oper_delete_item(STATE_LEAF, None, None, 0).unwrap();
+        session.apply_changes(None).unwrap();
+        assert_eq!(read(), 0);
+
+        // Discard all the push data.
+        session
+            .set_item_str(STATE_LEAF, Some("8"), None, 0)
+            .unwrap();
+        session.apply_changes(None).unwrap();
+        assert_eq!(read(), 1);
+        session.discard_oper_changes(None, None).unwrap();
+        assert_eq!(read(), 0);
+        let changes = session.get_oper_changes(&ctx, Some("test_module"));
+        assert!(changes.is_err_and(|e| e == SrError::NotFound));
+
+        session
+            .set_item_str(STATE_LEAF, Some("9"), None, 0)
+            .unwrap();
+        session.apply_changes(None).unwrap();
+        assert_eq!(read(), 1);
+    }
+
+    // Push data is removed with the session that stored it.
+    assert_eq!(read(), 0);
+}
+
+fn test_shared_handles() {
+    log_stderr(SrLogLevel::Error);
+    let _setup = Setup::setup_test_module();
+
+    let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
+        .expect("connect");
+    let mut running = connection
+        .new_session(SrDatastore::Running)
+        .expect("session");
+    let mut candidate = connection
+        .clone()
+        .new_session(SrDatastore::Candidate)
+        .expect("session");
+
+    // Sessions keep the connection open.
+    drop(connection);
+
+    let writer = std::thread::spawn(move || {
+        running.set_item_str(LEAF, Some("5"), None, 0).unwrap();
+        running.apply_changes(None).unwrap();
+        running
+    });
+    let mut running = writer.join().expect("writer thread");
+
+    candidate.set_item_str(LEAF, Some("6"), None, 0).unwrap();
+    candidate.apply_changes(None).unwrap();
+
+    let read = |session: &mut SrSession| {
+        let values = session.get_items(LEAF, None, 0).unwrap();
+        match values.get_value_mut(0).unwrap().data() {
+            value::Data::Int32(val) => *val,
+            _ => panic!("Wrong data type"),
+        }
+    };
+    assert_eq!(read(&mut running), 5);
+    assert_eq!(read(&mut candidate), 6);
+
+    // Threads share the session behind a mutex.
+    let shared = Arc::new(Mutex::new(running));
+    let reader = {
+        let shared = shared.clone();
+        std::thread::spawn(move || read(&mut shared.lock().unwrap()))
+    };
+    assert_eq!(reader.join().expect("reader thread"), 5);
+    assert_eq!(read(&mut shared.lock().unwrap()), 5);
+}
+
+fn test_nacm() {
+    log_stderr(SrLogLevel::Error);
+    let _setup = Setup::setup_test_module();
+    const NACM: &str = "/ietf-netconf-acm:nacm";
+    const CONT: &str = "/test_module:cont/l";
+
+    let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
+        .expect("connect");
+    let mut admin = connection
+        .new_session(SrDatastore::Running)
+        .expect("session");
+    let ctx = admin.get_context();
+
+    // Deny "alice" reading the container and executing the RPC.
+    let group = format!("{NACM}/groups/group[name='limited']/user-name");
+    admin.set_item_str(&group, Some("alice"), None, 0).unwrap();
+    let rules = format!("{NACM}/rule-list[name='limited']");
+    let config = [
+        ("group", "limited"),
+        ("rule[name='cont']/module-name", "test_module"),
+        ("rule[name='cont']/path", "/test_module:cont"),
+        ("rule[name='cont']/access-operations", "read"),
+        ("rule[name='cont']/action", "deny"),
+        ("rule[name='oper']/module-name", "test_module"),
+        ("rule[name='oper']/rpc-name", "oper"),
+        ("rule[name='oper']/access-operations", "exec"),
+        ("rule[name='oper']/action", "deny"),
+    ];
+    for (path, value) in config {
+        let path = format!("{rules}/{path}");
+        admin.set_item_str(&path, Some(value), None, 0).unwrap();
+    }
+    admin.set_item_str(LEAF, Some("1"), None, 0).unwrap();
+    admin.set_item_str(CONT, Some("secret"), None, 0).unwrap();
+    admin.apply_changes(None).unwrap();
+
+    let mut user = connection
+        .new_session(SrDatastore::Running)
+        .expect("session");
+    let nacm = user.nacm_init(0).expect("NACM init");
+    assert_eq!(user.nacm_user(), None);
+    assert_eq!(user.get_items(CONT, None, 0).unwrap().len(), 1);
+
+    user.set_nacm_user(Some("alice")).unwrap();
+    assert_eq!(user.nacm_user().as_deref(), Some("alice"));
+
+    // Reads are filtered.
+    assert_eq!(user.get_items(LEAF, None, 0).unwrap().len(), 1);
+    assert_eq!(user.get_items(CONT, None, 0).unwrap().len(), 0);
+    let data = user.get_data(
+        &ctx,
+        "/test_module:cont",
+        0,
+        None,
+        SrGetOptions::SR_OPER_DEFAULT,
+    );
+    assert!(data.is_err_and(|e| e == SrError::NotFound));
+
+    // Operations are checked.
+    let stats = user.nacm_glob_stats().unwrap();
+    let err = user
+        .nacm_check_path(&ctx, "/test_module:oper")
+        .expect_err("RPC is denied");
+    assert_eq!(err, SrError::Unauthorized);
+    let denied = user.nacm_glob_stats().unwrap().denied_operations;
+    assert!(denied > stats.denied_operations);
+
+    user.set_nacm_user(Some("bob")).unwrap();
+    assert!(user.nacm_check_path(&ctx, "/test_module:oper").is_ok());
+    assert_eq!(user.get_items(CONT, None, 0).unwrap().len(), 1);
+
+    user.set_nacm_user(None).unwrap();
+    assert_eq!(user.nacm_user(), None);
+    drop(nacm);
+
+    admin.remove_item(NACM, SrEditFlag::Default).unwrap();
+    admin.apply_changes(None).unwrap();
+}
+
+fn test_confirmed_commit() {
+    log_stderr(SrLogLevel::Error);
+    let _setup = Setup::setup_test_module();
+
+    let connection = SrConnection::new(ConnectionOptions::Datastore_Running)
+        .expect("connect");
+    let mut running = connection
+        .new_session(SrDatastore::Running)
+        .expect("session");
+    let mut candidate = connection
+        .new_session(SrDatastore::Candidate)
+        .expect("session");
+    let commit = SrConfirmedCommit::new(&connection).expect("commit");
+
+    running.set_item_str(LEAF, Some("1"), None, 0).unwrap();
+    running.apply_changes(None).unwrap();
+    let mut set_candidate = |value: &str| {
+        candidate.set_item_str(LEAF, Some(value), None, 0).unwrap();
+        candidate.apply_changes(None).unwrap();
+    };
+    let mut read = || {
+        let values = running.get_items(LEAF, None, 0).unwrap();
+        match values.get_value_mut(0).unwrap().data() {
+            value::Data::Int32(val) => *val,
+            _ => panic!("Wrong data type"),
+        }
+    };
+
+    // Rolled back when not confirmed in time.
+    set_candidate("2");
+    commit
+        .commit_confirmed(Duration::from_millis(200), None, None)
+        .unwrap();
+    assert!(commit.is_pending());
+    assert_eq!(read(), 2);
+    wait_for(|| !commit.is_pending());
+    assert!(commit.rollback_error().is_none());
+    assert_eq!(read(), 1);
+
+    // Confirmed with the persist ID.
+    commit
+        .commit_confirmed(Duration::from_secs(10), Some("token"), None)
+        .unwrap();
+    assert_eq!(read(), 2);
+    let err = commit.confirm(None).expect_err("Persist ID is required");
+    assert_eq!(err, SrError::InvalArg);
+    commit.confirm(Some("token")).unwrap();
+    assert!(!commit.is_pending());
+    assert_eq!(read(), 2);
+
+    // Cancelled.
+    set_candidate("3");
+    commit
+        .commit_confirmed(Duration::from_secs(10), None, None)
+        .unwrap();
+    assert_eq!(read(), 3);
+    commit.cancel(None).unwrap();
+    assert_eq!(read(), 2);
+    let err = commit.cancel(None).expect_err("Nothing to cancel");
+    assert_eq!(err, SrError::NotFound);
+
+    // Closing rolls back a persistent commit too.
+    set_candidate("4");
+    commit
+        .commit_confirmed(Duration::from_secs(10), Some("token"), None)
+        .unwrap();
+    assert_eq!(read(), 4);
+    commit.close().unwrap();
+    assert_eq!(read(), 2);
+
+    // A persistent commit outlives the handle until it expires.
+    let commit = SrConfirmedCommit::new(&connection).expect("commit");
+    commit
+        .commit_confirmed(Duration::from_millis(200), Some("token"), None)
+        .unwrap();
+    drop(commit);
+    assert_eq!(read(), 4);
+    wait_for(|| read() == 2);
+}
+
+/// Wait for the condition, failing after a few seconds.
+fn wait_for(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "Timed out waiting");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
@@ -2,10 +2,14 @@ use crate::common::Setup;
 use std::ops::{AddAssign, DerefMut};
 use std::sync::{Arc, Mutex};
 use sysrepo::connection::{ConnectionOptions, SrConnection};
-use sysrepo::enums::{SrDatastore, SrLogLevel};
-use sysrepo::errors::SrError;
+use sysrepo::enums::{SrDatastore, SrEditFlag, SrLogLevel, SrMovePosition};
+use sysrepo::errors::{SrError, SrErrorInfo};
 use sysrepo::log_stderr;
-use sysrepo::session::{SrEvent, SrSession};
+use sysrepo::session::{
+    SrChangeOperation, SrChangeOperationTree, SrEvent, SrSession,
+};
+use yang3::data::{Data, DataFormat};
+use yang3::schema::DataValue;
 
 pub mod common;
 
@@ -15,14 +19,33 @@ fn test_subscriptions() {
 
     test_module_change::test_call_module_container_value_change();
     test_module_change::test_call_module_change();
+    test_module_change::test_move_item_reports_moved();
+    test_module_change::test_change_originator();
+    test_module_change::test_changes_iter_tree();
+    test_module_change::test_change_diff();
 
     test_oper_get_subscribe::test_call_module_container_value_change();
+    test_oper_get_subscribe::test_oper_poll_cached();
+    test_oper_get_subscribe::test_oper_poll_diff();
 
     test_rpc_subscribe::test_on_rpc_subscribe();
     test_rpc_subscribe::test_on_rpc_subscribe_tree();
 
     test_on_notification_subscribe::test_on_notification_subscribe();
     test_on_notification_subscribe::test_on_notification_subscribe_tree();
+    test_on_notification_subscribe::test_on_notification_subscribe_stream();
+    test_on_notification_subscribe::test_notification_stream_drops_when_full();
+    test_on_notification_subscribe::test_modify_notification_subscription();
+
+    test_callback_errors::test_module_change_netconf_error();
+    test_callback_errors::test_rpc_error_message();
+    test_callback_errors::test_panicking_callback();
+    test_callback_errors::test_panicking_oper_get_callback();
+
+    test_subscription_lifecycle::test_shared_context();
+    test_subscription_lifecycle::test_remove_subscription();
+    test_subscription_lifecycle::test_callbacks_released();
+    test_subscription_lifecycle::test_subscription_keeps_session();
 }
 
 mod test_module_change {
@@ -36,13 +59,13 @@ mod test_module_change {
         let session = connection.start_session(SrDatastore::Running).unwrap();
         let check = Arc::new(Mutex::new(0));
         let change_cb_value = check.clone();
-        let callback = |_session: SrSession,
-                        _sub_id: u32,
-                        _module_name: &str,
-                        _xpath: Option<&str>,
-                        _event: SrEvent,
-                        _request_id: u32|
-         -> Result<(), SrError> {
+        let callback = move |_session: &mut SrSession,
+                             _sub_id: u32,
+                             _module_name: &str,
+                             _xpath: Option<&str>,
+                             _event: SrEvent,
+                             _request_id: u32|
+              -> Result<(), SrErrorInfo> {
             change_cb_value.lock().unwrap().deref_mut().add_assign(1);
             Ok(())
         };
@@ -78,13 +101,13 @@ mod test_module_change {
         let session = connection.start_session(SrDatastore::Running).unwrap();
         let check = Arc::new(Mutex::new(0));
         let change_cb_value = check.clone();
-        let callback = |_session: SrSession,
-                        _sub_id: u32,
-                        _module_name: &str,
-                        _xpath: Option<&str>,
-                        _event: SrEvent,
-                        _request_id: u32|
-         -> Result<(), SrError> {
+        let callback = move |_session: &mut SrSession,
+                             _sub_id: u32,
+                             _module_name: &str,
+                             _xpath: Option<&str>,
+                             _event: SrEvent,
+                             _request_id: u32|
+              -> Result<(), SrErrorInfo> {
             change_cb_value.lock().unwrap().deref_mut().add_assign(1);
             Ok(())
         };
@@ -106,11 +129,387 @@ mod test_module_change {
         // Change is called 2 times
         assert_eq!(*check.lock().unwrap(), 2);
     }
+
+    pub fn test_move_item_reports_moved() {
+        log_stderr(SrLogLevel::Error);
+        let _setup = Setup::setup_test_module();
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+
+        for name in ["a", "b", "c"] {
+            let path = format!("/test_module:ordered-list[name='{name}']");
+            session.set_item_str(&path, None, None, 0).unwrap();
+        }
+        for value in ["1", "2", "3"] {
+            session
+                .set_item_str("/test_module:values", Some(value), None, 0)
+                .unwrap();
+        }
+        session.apply_changes(None).unwrap();
+
+        let moved = Arc::new(Mutex::new(Vec::new()));
+        let moved_cb = moved.clone();
+        let callback = move |session: &mut SrSession,
+                             _sub_id: u32,
+                             _module_name: &str,
+                             _xpath: Option<&str>,
+                             event: SrEvent,
+                             _request_id: u32|
+              -> Result<(), SrErrorInfo> {
+            if event != SrEvent::Done {
+                return Ok(());
+            }
+            for change in session.get_changes_iter("/test_module:*//.")? {
+                if let SrChangeOperation::Moved(data) = change {
+                    let prev = data.prev_value.map(|prev| prev.xpath());
+                    moved_cb.lock().unwrap().push((data.value.xpath(), prev));
+                }
+            }
+            Ok(())
+        };
+        session
+            .on_module_change_subscribe("test_module", None, callback, 0, 0)
+            .unwrap();
+
+        session
+            .move_item(
+                "/test_module:ordered-list[name='c']",
+                SrMovePosition::After,
+                Some("[name='a']"),
+                None,
+                None,
+                0,
+            )
+            .unwrap();
+        session.apply_changes(None).unwrap();
+
+        assert_eq!(
+            *moved.lock().unwrap(),
+            vec![(
+                "/test_module:ordered-list[name='c']".to_string(),
+                Some("/test_module:ordered-list[name='a']".to_string())
+            )]
+        );
+        moved.lock().unwrap().clear();
+
+        session
+            .move_item(
+                "/test_module:values[.='3']",
+                SrMovePosition::First,
+                None,
+                None,
+                None,
+                0,
+            )
+            .unwrap();
+        session.apply_changes(None).unwrap();
+
+        assert_eq!(
+            *moved.lock().unwrap(),
+            vec![("/test_module:values[.='3']".to_string(), None)]
+        );
+
+        let values = session.get_items("/test_module:values", None, 0).unwrap();
+        let values = (0..values.len())
+            .map(|i| values.get_value_mut(i).unwrap().xpath())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            values,
+            vec![
+                "/test_module:values[.='3']",
+                "/test_module:values[.='1']",
+                "/test_module:values[.='2']",
+            ]
+        );
+    }
+
+    pub fn test_change_originator() {
+        log_stderr(SrLogLevel::Error);
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let mut writer = connection.new_session(SrDatastore::Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        session.set_orig_name("handler").unwrap();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let events_cb = events.clone();
+        let callback = move |session: &mut SrSession,
+                             _sub_id: u32,
+                             _module_name: &str,
+                             _xpath: Option<&str>,
+                             event: SrEvent,
+                             _request_id: u32|
+              -> Result<(), SrErrorInfo> {
+            // Ignore the changes made by the handler itself.
+            let name = session.get_orig_name();
+            if event != SrEvent::Done || name.as_deref() == Some("handler") {
+                return Ok(());
+            }
+            events_cb.lock().unwrap().push((
+                name,
+                session.get_orig_data(0).ok(),
+                session.get_event_sid(),
+                session.get_event_user().is_some(),
+            ));
+            Ok(())
+        };
+        session
+            .on_module_change_subscribe("examples", None, callback, 0, 0)
+            .unwrap();
+
+        session
+            .set_item_str("/examples:cont/l", Some("handler"), None, 0)
+            .unwrap();
+        session.apply_changes(None).unwrap();
+        assert!(events.lock().unwrap().is_empty());
+
+        writer.set_orig_name("cli").unwrap();
+        writer.push_orig_data(b"admin").unwrap();
+        assert_eq!(writer.get_orig_name().as_deref(), Some("cli"));
+        assert_eq!(writer.get_orig_data(0).unwrap(), b"admin");
+        writer
+            .set_item_str("/examples:cont/l", Some("cli"), None, 0)
+            .unwrap();
+        writer.apply_changes(None).unwrap();
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![(
+                Some("cli".to_string()),
+                Some(b"admin".to_vec()),
+                writer.get_id(),
+                true
+            )]
+        );
+
+        writer.del_orig_data();
+        assert!(writer.get_orig_data(0).is_err());
+    }
+
+    pub fn test_changes_iter_tree() {
+        log_stderr(SrLogLevel::Error);
+        let _setup = Setup::setup_test_module();
+        const LIST: &str = "/test_module:cont/sub/test-list";
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        for (name, val) in [("a", "1"), ("b", "2")] {
+            let path = format!("{LIST}[name='{name}']/val");
+            session.set_item_str(&path, Some(val), None, 0).unwrap();
+        }
+        for name in ["a", "b", "c"] {
+            let path = format!("/test_module:ordered-list[name='{name}']");
+            session.set_item_str(&path, None, None, 0).unwrap();
+        }
+        session.apply_changes(None).unwrap();
+
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let changes_cb = changes.clone();
+        let callback = move |session: &mut SrSession,
+                             _sub_id: u32,
+                             _module_name: &str,
+                             _xpath: Option<&str>,
+                             event: SrEvent,
+                             _request_id: u32|
+              -> Result<(), SrErrorInfo> {
+            if event != SrEvent::Done {
+                return Ok(());
+            }
+            let iter = session.get_changes_iter_tree("/test_module:*//.")?;
+            for change in &iter {
+                let oper = match change {
+                    SrChangeOperationTree::Created(_) => "created",
+                    SrChangeOperationTree::Modified(_) => "modified",
+                    SrChangeOperationTree::Deleted(_) => "deleted",
+                    SrChangeOperationTree::Moved(_) => "moved",
+                };
+                let data = change.data();
+                let node = data.node();
+                changes_cb.lock().unwrap().push((
+                    oper,
+                    node.path(),
+                    node.value(),
+                    data.prev_value.clone(),
+                    data.prev_list.clone(),
+                    data.prev_default_value,
+                ));
+            }
+            Ok(())
+        };
+        session
+            .on_module_change_subscribe("test_module", None, callback, 0, 0)
+            .unwrap();
+
+        let path = format!("{LIST}[name='c']/val");
+        session.set_item_str(&path, Some("3"), None, 0).unwrap();
+        let path = format!("{LIST}[name='a']/val");
+        session.set_item_str(&path, Some("10"), None, 0).unwrap();
+        session
+            .set_item_str("/test_module:leafWithDefault", Some("5"), None, 0)
+            .unwrap();
+        let path = format!("{LIST}[name='b']");
+        session.remove_item(&path, SrEditFlag::Default).unwrap();
+        session
+            .move_item(
+                "/test_module:ordered-list[name='c']",
+                SrMovePosition::After,
+                Some("[name='a']"),
+                None,
+                None,
+                0,
+            )
+            .unwrap();
+        session.apply_changes(None).unwrap();
+
+        let changes = changes.lock().unwrap();
+        let expected = [
+            (
+                "created",
+                format!("{LIST}[name='c']"),
+                None,
+                None,
+                None,
+                false,
+            ),
+            (
+                "created",
+                format!("{LIST}[name='c']/val"),
+                Some(DataValue::Other("3".to_string())),
+                None,
+                None,
+                false,
+            ),
+            (
+                "modified",
+                format!("{LIST}[name='a']/val"),
+                Some(DataValue::Other("10".to_string())),
+                Some(DataValue::Other("1".to_string())),
+                None,
+                false,
+            ),
+            (
+                "modified",
+                "/test_module:leafWithDefault".to_string(),
+                Some(DataValue::Int32(5)),
+                Some(DataValue::Int32(123)),
+                None,
+                true,
+            ),
+            (
+                "deleted",
+                format!("{LIST}[name='b']"),
+                None,
+                None,
+                None,
+                false,
+            ),
+            (
+                "moved",
+                "/test_module:ordered-list[name='c']".to_string(),
+                None,
+                None,
+                Some("[name='a']".to_string()),
+                false,
+            ),
+        ];
+        for change in expected {
+            assert!(changes.contains(&change), "{change:?} in {changes:?}");
+        }
+    }
+
+    pub fn test_change_diff() {
+        log_stderr(SrLogLevel::Error);
+        let _setup = Setup::setup_test_module();
+        const LIST: &str = "/test_module:cont/sub/test-list";
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        for name in ["a", "b"] {
+            let path = format!("{LIST}[name='{name}']/val");
+            session.set_item_str(&path, Some(name), None, 0).unwrap();
+        }
+        session.apply_changes(None).unwrap();
+
+        let diffs = Arc::new(Mutex::new(Vec::new()));
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let diffs_cb = diffs.clone();
+        let changes_cb = changes.clone();
+        let callback = move |session: &mut SrSession,
+                             _sub_id: u32,
+                             _module_name: &str,
+                             _xpath: Option<&str>,
+                             event: SrEvent,
+                             _request_id: u32|
+              -> Result<(), SrErrorInfo> {
+            if event != SrEvent::Change {
+                return Ok(());
+            }
+            for format in [DataFormat::XML, DataFormat::JSON, DataFormat::LYB] {
+                let diff = session.print_change_diff(format)?;
+                diffs_cb.lock().unwrap().push(diff);
+            }
+
+            // Every change reported by the iterator is in the diff.
+            let ctx = session.get_context();
+            let diff = session.get_change_diff(&ctx)?;
+            for change in session.get_changes_iter("/test_module:*//.")? {
+                let value = match change {
+                    SrChangeOperation::Created(data)
+                    | SrChangeOperation::Modified(data)
+                    | SrChangeOperation::Deleted(data)
+                    | SrChangeOperation::Moved(data) => data.value,
+                };
+                let xpath = value.xpath();
+                let found = diff.find_path(&xpath).is_ok();
+                changes_cb.lock().unwrap().push((xpath, found));
+            }
+            Ok(())
+        };
+        session
+            .on_module_change_subscribe("test_module", None, callback, 0, 0)
+            .unwrap();
+
+        let path = format!("{LIST}[name='c']/val");
+        session.set_item_str(&path, Some("c"), None, 0).unwrap();
+        let path = format!("{LIST}[name='a']/val");
+        session.set_item_str(&path, Some("A"), None, 0).unwrap();
+        let path = format!("{LIST}[name='b']");
+        session.remove_item(&path, SrEditFlag::Default).unwrap();
+        session.apply_changes(None).unwrap();
+
+        let diffs = diffs.lock().unwrap();
+        assert_eq!(diffs.len(), 3);
+        let xml = String::from_utf8(diffs[0].clone()).unwrap();
+        assert!(xml.contains("yang:operation=\"create\""));
+        assert!(xml.contains("yang:operation=\"replace\""));
+        assert!(xml.contains("yang:operation=\"delete\""));
+        let json = String::from_utf8(diffs[1].clone()).unwrap();
+        assert!(json.contains("\"yang:operation\": \"create\""));
+        assert!(!diffs[2].is_empty());
+
+        let changes = changes.lock().unwrap();
+        for xpath in [
+            format!("{LIST}[name='c']"),
+            format!("{LIST}[name='a']/val"),
+            format!("{LIST}[name='b']"),
+        ] {
+            assert!(changes.contains(&(xpath, true)), "{changes:?}");
+        }
+        assert!(changes.iter().all(|(_, found)| *found), "{changes:?}");
+    }
 }
 
 mod test_oper_get_subscribe {
     use super::*;
-    use sysrepo::enums::SrGetOptions;
+    use std::sync::{mpsc, Condvar};
+    use std::time::Duration;
+    use sysrepo::enums::{SrGetOptions, SrSubcribeFlag};
+    use sysrepo::value::Data;
     use yang3::data::{DataDiffFlags, DataTree};
 
     pub fn test_call_module_container_value_change() {
@@ -165,6 +564,164 @@ mod test_oper_get_subscribe {
         let diff = diff.unwrap();
         assert_eq!(diff.iter().count(), 0);
     }
+
+    pub fn test_oper_poll_cached() {
+        log_stderr(SrLogLevel::Error);
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Operational)
+                .unwrap();
+        let session =
+            connection.start_session(SrDatastore::Operational).unwrap();
+        let calls = Arc::new((Mutex::new(0u64), Condvar::new()));
+        let calls_cb = calls.clone();
+
+        session
+            .on_oper_get_subscribe(
+                "examples",
+                "/examples:stats",
+                move |_sess,
+                      ctx,
+                      _u_id,
+                      _path,
+                      _request,
+                      _xpath,
+                      _id,
+                      _data| {
+                    let (calls, polled) = &*calls_cb;
+                    let mut calls = calls.lock().unwrap();
+                    *calls += 1;
+                    polled.notify_all();
+                    let mut node = DataTree::new(ctx);
+                    node.new_path(
+                        "/examples:stats/counter",
+                        Some(&calls.to_string()),
+                        false,
+                    )
+                    .map_err(|_e| SrError::Internal)?;
+                    Ok(Some(node))
+                },
+                0,
+            )
+            .unwrap();
+        session
+            .on_oper_poll_subscribe("examples", "/examples:stats", 60_000, 0)
+            .unwrap();
+
+        // Wait for the poll subscription to fill the cache.
+        {
+            let (calls, polled) = &*calls;
+            let (_calls, timeout) = polled
+                .wait_timeout_while(
+                    calls.lock().unwrap(),
+                    Duration::from_secs(5),
+                    |calls| *calls == 0,
+                )
+                .unwrap();
+            assert!(!timeout.timed_out());
+        }
+
+        let mut read = |opts: SrGetOptions| {
+            let values = session
+                .get_items("/examples:stats/counter", None, opts.bits())
+                .unwrap();
+            match values.get_value_mut(0).unwrap().data() {
+                Data::UInt64(val) => *val,
+                _ => panic!("Expected a uint64 counter"),
+            }
+        };
+
+        // Cached provider data is returned until it expires.
+        let cached = read(SrGetOptions::SR_OPER_DEFAULT);
+        assert_eq!(read(SrGetOptions::SR_OPER_DEFAULT), cached);
+
+        // Fresh data calls the provider again.
+        let fresh = read(SrGetOptions::SR_OPER_NO_POLL_CACHED);
+        assert!(fresh > cached);
+        assert_eq!(*calls.0.lock().unwrap(), fresh);
+    }
+
+    pub fn test_oper_poll_diff() {
+        log_stderr(SrLogLevel::Error);
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Operational)
+                .unwrap();
+        let session =
+            connection.start_session(SrDatastore::Operational).unwrap();
+        let calls = Arc::new(Mutex::new(0u64));
+        let (sender, receiver) = mpsc::channel();
+
+        // Every cache refresh reports the new counter as a change.
+        session
+            .on_module_change_subscribe(
+                "examples",
+                None,
+                move |session: &mut SrSession,
+                      _sub_id: u32,
+                      _module_name: &str,
+                      _xpath: Option<&str>,
+                      _event: SrEvent,
+                      _request_id: u32|
+                      -> Result<(), SrErrorInfo> {
+                    for change in
+                        session.get_changes_iter("/examples:stats/counter")?
+                    {
+                        let value = match change {
+                            SrChangeOperation::Created(data)
+                            | SrChangeOperation::Modified(data)
+                            | SrChangeOperation::Deleted(data)
+                            | SrChangeOperation::Moved(data) => data.value,
+                        };
+                        let _ = sender.send(value.xpath());
+                    }
+                    Ok(())
+                },
+                0,
+                0,
+            )
+            .unwrap();
+        session
+            .on_oper_get_subscribe(
+                "examples",
+                "/examples:stats",
+                move |_sess,
+                      ctx,
+                      _u_id,
+                      _path,
+                      _request,
+                      _xpath,
+                      _id,
+                      _data| {
+                    let mut calls = calls.lock().unwrap();
+                    *calls += 1;
+                    let mut node = DataTree::new(ctx);
+                    node.new_path(
+                        "/examples:stats/counter",
+                        Some(&calls.to_string()),
+                        false,
+                    )
+                    .map_err(|_e| SrError::Internal)?;
+                    Ok(Some(node))
+                },
+                0,
+            )
+            .unwrap();
+        session
+            .on_oper_poll_subscribe(
+                "examples",
+                "/examples:stats",
+                100,
+                SrSubcribeFlag::OperPollDiff as u32,
+            )
+            .unwrap();
+
+        // The first poll creates the counter, the next ones modify it.
+        for _ in 0..2 {
+            let xpath = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(xpath, "/examples:stats/counter");
+        }
+    }
 }
 
 mod test_rpc_subscribe {
@@ -193,7 +750,7 @@ mod test_rpc_subscribe {
                     Data::Int64(123),
                     false,
                 );
-                output
+                Ok(output)
             },
             0,
             0,
@@ -251,6 +808,7 @@ mod test_rpc_subscribe {
              _request_id| {
                 let _r =
                     output.new_path("/examples:oper/ret", Some("123"), true);
+                Ok(())
             },
             0,
             0,
@@ -264,7 +822,7 @@ mod test_rpc_subscribe {
             .unwrap();
         let _r = input.new_path("/examples:oper/arg2", Some("1"), false);
 
-        let data = session.rpc_send_tree(&ctx, Some(input), None);
+        let data = session.rpc_send_tree(&ctx, Some(&input), None);
         assert!(data.is_ok());
         let data = data.unwrap();
         let output_path = "/examples:oper/ret";
@@ -283,10 +841,12 @@ mod test_rpc_subscribe {
 
 mod test_on_notification_subscribe {
     use super::*;
+    use std::time::{Duration, SystemTime};
     use sysrepo::enums::SrNotifType;
+    use sysrepo::subscription::{SrNotifStreamOptions, SrSubscription};
     use sysrepo::value::Data;
     use sysrepo::values::SrValues;
-    use yang3::data::{Data as yang_data, DataTree};
+    use yang3::data::{Data as yang_data, DataFormat, DataTree};
     use yang3::schema::DataValue;
 
     pub fn test_on_notification_subscribe() {
@@ -392,4 +952,495 @@ mod test_on_notification_subscribe {
         assert!(r.is_ok());
         session.notif_send_tree(&notf_node, 0, 1).unwrap()
     }
+
+    pub fn test_on_notification_subscribe_stream() {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let (_subscription, receiver) = session
+            .on_notif_subscribe_stream(
+                "examples",
+                Some("/examples:notif"),
+                None,
+                None,
+                SrNotifStreamOptions::default(),
+                0,
+            )
+            .unwrap();
+
+        let ctx = session.get_context();
+        let mut notf_node = DataTree::new(&ctx);
+        notf_node
+            .new_path("/examples:notif/val", Some("123.0"), false)
+            .unwrap();
+        session.notif_send_tree(&notf_node, 0, 1).unwrap();
+
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event.notif_type, SrNotifType::Realtime);
+        assert!(event
+            .timestamp
+            .is_some_and(|time| time <= SystemTime::now()));
+
+        // The event outlives the callback and can be parsed again.
+        let tree = event.tree(&ctx).expect("notification").unwrap();
+        let value_node = tree.find_path("/examples:notif/val").unwrap();
+        assert_eq!(
+            value_node.value(),
+            Some(DataValue::Other("123.0".to_string()))
+        );
+    }
+
+    pub fn test_modify_notification_subscription() {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_cb = received.clone();
+        let sub_id = Arc::new(Mutex::new(None));
+        let sub_id_cb = sub_id.clone();
+        let before = SystemTime::now();
+        let subscription = SrSubscription::on_notification_subscribe_tree(
+            session,
+            "examples",
+            Some("/examples:notif"),
+            None,
+            None,
+            move |_session, id, notif_type, _node, timestamp| {
+                *sub_id_cb.lock().unwrap() = Some(id);
+                received_cb.lock().unwrap().push((notif_type, timestamp));
+            },
+            0,
+        )
+        .unwrap();
+
+        let ctx = session.get_context();
+        let mut notf_node = DataTree::new(&ctx);
+        notf_node
+            .new_path("/examples:notif/val", Some("1.0"), false)
+            .unwrap();
+        session.notif_send_tree(&notf_node, 0, 1).unwrap();
+        {
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 1);
+            assert_eq!(received[0].0, SrNotifType::Realtime);
+            assert!(received[0]
+                .1
+                .is_some_and(|time| time >= before - Duration::from_secs(1)));
+        }
+
+        // Filter out every notification.
+        let id = sub_id.lock().unwrap().expect("sub id");
+        subscription
+            .modify_notif_xpath(id, Some("/examples:notif[val='2.0']"))
+            .unwrap();
+        session.notif_send_tree(&notf_node, 0, 1).unwrap();
+
+        subscription
+            .modify_notif_stop_time(id, Some(SystemTime::now()))
+            .unwrap();
+
+        let received = received.lock().unwrap();
+        assert!(!received
+            .iter()
+            .skip(1)
+            .any(|(notif_type, _)| *notif_type == SrNotifType::Realtime));
+        assert!(received
+            .iter()
+            .any(|(notif_type, _)| *notif_type == SrNotifType::Modified));
+    }
+
+    pub fn test_notification_stream_drops_when_full() {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_StartUp).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let options = SrNotifStreamOptions {
+            capacity: 1,
+            block: false,
+            format: DataFormat::XML,
+        };
+        let (_subscription, receiver) = session
+            .on_notif_subscribe_stream(
+                "examples",
+                Some("/examples:notif"),
+                None,
+                None,
+                options,
+                0,
+            )
+            .unwrap();
+
+        let ctx = session.get_context();
+        let mut notf_node = DataTree::new(&ctx);
+        notf_node
+            .new_path("/examples:notif/val", Some("1.0"), false)
+            .unwrap();
+        for _ in 0..3 {
+            session.notif_send_tree(&notf_node, 0, 1).unwrap();
+        }
+
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(event.data.unwrap().contains("<notif"));
+        assert!(receiver.try_recv().is_err());
+    }
+}
+
+mod test_callback_errors {
+    use super::*;
+    use sysrepo::errors::NetconfError;
+    use sysrepo::subscription::set_callback_panic_hook;
+
+    pub fn test_module_change_netconf_error() {
+        log_stderr(SrLogLevel::Error);
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+
+        let callback = |_session: &mut SrSession,
+                        _sub_id: u32,
+                        _module_name: &str,
+                        _xpath: Option<&str>,
+                        event: SrEvent,
+                        _request_id: u32|
+         -> Result<(), SrErrorInfo> {
+            if event != SrEvent::Change {
+                return Ok(());
+            }
+            Err(SrErrorInfo::netconf(
+                SrError::CallbackFailed,
+                NetconfError {
+                    error_type: "application".to_string(),
+                    error_tag: "invalid-value".to_string(),
+                    error_app_tag: Some("value-rejected".to_string()),
+                    error_path: Some("/examples:cont/l".to_string()),
+                    error_message: "Value rejected".to_string(),
+                    error_info: vec![(
+                        "bad-element".to_string(),
+                        "l".to_string(),
+                    )],
+                },
+            ))
+        };
+        session
+            .on_module_change_subscribe(
+                "examples",
+                Some("/examples:cont/l"),
+                callback,
+                0,
+                0,
+            )
+            .unwrap();
+
+        session
+            .set_item_str("/examples:cont/l", Some("rejected"), None, 0)
+            .unwrap();
+        let err = session.apply_changes(None).expect_err("Callback fails");
+        session.discard_changes().unwrap();
+
+        assert_eq!(err, SrError::CallbackFailed);
+        assert_eq!(err.path(), Some("/examples:cont/l"));
+        let netconf = err.netconf_error().expect("NETCONF error");
+        assert_eq!(netconf.error_type, "application");
+        assert_eq!(netconf.error_tag, "invalid-value");
+        assert_eq!(netconf.error_app_tag.as_deref(), Some("value-rejected"));
+        assert_eq!(netconf.error_message, "Value rejected");
+        assert_eq!(
+            netconf.error_info,
+            vec![("bad-element".to_string(), "l".to_string())]
+        );
+    }
+
+    pub fn test_rpc_error_message() {
+        log_stderr(SrLogLevel::Error);
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+
+        session
+            .on_rpc_subscribe(
+                Some("/examples:oper"),
+                |_session, _sub_id, _xpath, _inputs, _event, _request_id| {
+                    Err(SrErrorInfo::new(
+                        SrError::OperationFailed,
+                        "Operation not possible",
+                    ))
+                },
+                0,
+                0,
+            )
+            .unwrap();
+
+        let err = session
+            .rpc_send("/examples:oper", None, None)
+            .expect_err("RPC fails");
+        assert_eq!(err, SrError::OperationFailed);
+        assert!(err
+            .errors()
+            .iter()
+            .any(|err| err.message == "Operation not possible"));
+    }
+
+    pub fn test_panicking_callback() {
+        log_stderr(SrLogLevel::Error);
+
+        let panics = Arc::new(Mutex::new(Vec::new()));
+        let panics_hook = panics.clone();
+        set_callback_panic_hook(move |message| {
+            panics_hook.lock().unwrap().push(message.to_string());
+        });
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        session
+            .on_module_change_subscribe(
+                "examples",
+                Some("/examples:cont/l"),
+                |_session: &mut SrSession,
+                 _sub_id: u32,
+                 _module_name: &str,
+                 _xpath: Option<&str>,
+                 event: SrEvent,
+                 _request_id: u32|
+                 -> Result<(), SrErrorInfo> {
+                    if event == SrEvent::Change {
+                        panic!("boom");
+                    }
+                    Ok(())
+                },
+                0,
+                0,
+            )
+            .unwrap();
+
+        session
+            .set_item_str("/examples:cont/l", Some("panic"), None, 0)
+            .unwrap();
+        let err = session.apply_changes(None).unwrap_err();
+        assert_eq!(err.code(), SrError::CallbackFailed);
+        assert!(err.errors().iter().any(|err| err.message.contains("boom")));
+        session.discard_changes().unwrap();
+
+        assert_eq!(*panics.lock().unwrap(), vec!["boom".to_string()]);
+    }
+
+    pub fn test_panicking_oper_get_callback() {
+        log_stderr(SrLogLevel::Error);
+
+        let panics = Arc::new(Mutex::new(Vec::new()));
+        let panics_hook = panics.clone();
+        set_callback_panic_hook(move |message| {
+            panics_hook.lock().unwrap().push(message.to_string());
+        });
+
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Operational)
+                .unwrap();
+        let session =
+            connection.start_session(SrDatastore::Operational).unwrap();
+
+        // Nested provider, sysrepo passes the parent it owns.
+        session
+            .on_oper_get_subscribe(
+                "examples",
+                "/examples:stats/counter",
+                |_sess,
+                 _ctx,
+                 _sub_id,
+                 _module,
+                 _path,
+                 _request,
+                 _id,
+                 parent| {
+                    assert!(parent.is_some());
+                    panic!("boom");
+                },
+                0,
+            )
+            .unwrap();
+
+        // The parent is not freed by the failed callback.
+        for _ in 0..2 {
+            let err = session
+                .get_items("/examples:stats/counter", None, 0)
+                .unwrap_err();
+            assert_eq!(err.code(), SrError::CallbackFailed);
+        }
+
+        assert_eq!(*panics.lock().unwrap(), vec!["boom"; 2]);
+    }
+}
+
+mod test_subscription_lifecycle {
+    use super::*;
+    use sysrepo::subscription::SrSubscription;
+
+    fn counter(
+        count: &Arc<Mutex<u32>>,
+    ) -> impl FnMut(
+        &mut SrSession,
+        u32,
+        &str,
+        Option<&str>,
+        SrEvent,
+        u32,
+    ) -> Result<(), SrErrorInfo> {
+        let count = count.clone();
+        move |_session: &mut SrSession,
+              _sub_id: u32,
+              _module_name: &str,
+              _xpath: Option<&str>,
+              _event: SrEvent,
+              _request_id: u32| {
+            count.lock().unwrap().add_assign(1);
+            Ok(())
+        }
+    }
+
+    fn change(session: &mut SrSession, value: &str) {
+        session
+            .set_item_str("/examples:cont/l", Some(value), None, 0)
+            .unwrap();
+        session.apply_changes(None).unwrap();
+    }
+
+    pub fn test_shared_context() {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let first = Arc::new(Mutex::new(0));
+        let second = Arc::new(Mutex::new(0));
+
+        let mut subscription = SrSubscription::on_module_change(
+            session,
+            "examples",
+            Some("/examples:cont/l"),
+            counter(&first),
+            0,
+            0,
+        )
+        .unwrap();
+        let first_id = subscription.last_sub_id();
+        let second_id = subscription
+            .add_module_change(
+                session,
+                "examples",
+                None,
+                counter(&second),
+                0,
+                0,
+            )
+            .unwrap();
+        assert_ne!(first_id, second_id);
+        assert_eq!(subscription.last_sub_id(), second_id);
+
+        // Change and done events for both subscriptions.
+        change(session, "shared-1");
+        assert_eq!(*first.lock().unwrap(), 2);
+        assert_eq!(*second.lock().unwrap(), 2);
+
+        subscription.suspend(first_id).unwrap();
+        assert!(subscription.is_suspended(first_id).unwrap());
+        assert!(!subscription.is_suspended(second_id).unwrap());
+        change(session, "shared-2");
+        assert_eq!(*first.lock().unwrap(), 2);
+        assert_eq!(*second.lock().unwrap(), 4);
+
+        subscription.resume(first_id).unwrap();
+        assert!(!subscription.is_suspended(first_id).unwrap());
+
+        subscription.unsubscribe_sub(second_id).unwrap();
+        change(session, "shared-3");
+        assert_eq!(*first.lock().unwrap(), 4);
+        assert_eq!(*second.lock().unwrap(), 4);
+
+        assert!(subscription.is_suspended(second_id).is_err());
+    }
+
+    pub fn test_remove_subscription() {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let count = Arc::new(Mutex::new(0));
+
+        let id = session
+            .on_module_change_subscribe("examples", None, counter(&count), 0, 0)
+            .unwrap()
+            .id();
+        change(session, "removed-1");
+        assert_eq!(*count.lock().unwrap(), 2);
+
+        let subscription = session.remove_subscription(&id);
+        assert!(subscription.is_some());
+        drop(subscription);
+        change(session, "removed-2");
+        assert_eq!(*count.lock().unwrap(), 2);
+    }
+
+    pub fn test_callbacks_released() {
+        let mut connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.start_session(SrDatastore::Running).unwrap();
+        let count = Arc::new(Mutex::new(0));
+
+        let mut subscription = SrSubscription::on_module_change(
+            session,
+            "examples",
+            None,
+            counter(&count),
+            0,
+            0,
+        )
+        .unwrap();
+        let sub_id = subscription
+            .add_module_change(session, "examples", None, counter(&count), 0, 0)
+            .unwrap();
+        assert_eq!(Arc::strong_count(&count), 3);
+
+        subscription.unsubscribe_sub(sub_id).unwrap();
+        assert_eq!(Arc::strong_count(&count), 2);
+
+        drop(subscription);
+        assert_eq!(Arc::strong_count(&count), 1);
+
+        let id = session
+            .on_module_change_subscribe("examples", None, counter(&count), 0, 0)
+            .unwrap()
+            .id();
+        assert_eq!(Arc::strong_count(&count), 2);
+        drop(session.remove_subscription(&id));
+        assert_eq!(Arc::strong_count(&count), 1);
+    }
+
+    pub fn test_subscription_keeps_session() {
+        let connection =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let session = connection.new_session(SrDatastore::Running).unwrap();
+        let count = Arc::new(Mutex::new(0));
+
+        let subscription = SrSubscription::on_module_change(
+            &session,
+            "examples",
+            None,
+            counter(&count),
+            0,
+            0,
+        )
+        .unwrap();
+
+        // The subscription keeps its session and connection open.
+        drop(session);
+        drop(connection);
+        let other =
+            SrConnection::new(ConnectionOptions::Datastore_Running).unwrap();
+        let mut other_session =
+            other.new_session(SrDatastore::Running).unwrap();
+        change(&mut other_session, "kept-1");
+        assert_eq!(*count.lock().unwrap(), 2);
+
+        drop(subscription);
+        change(&mut other_session, "kept-2");
+        assert_eq!(*count.lock().unwrap(), 2);
+    }
 }